@@ -1,12 +1,12 @@
 use caemu::component::{Component, In, Out, InBus, OutBus, InOutBus};
 use caemu::delay::Delay;
 use caemu::bus::{Bus};
-use caemu_macro::comp;
+use caemu_macro::{comp, Snapshot};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
-#[derive(Default)]
+#[derive(Default, Snapshot)]
 struct State {
     pc: u16,
     sp: u8,
@@ -22,7 +22,7 @@ impl State {
     }
 }
 
-#[comp]
+#[comp(snapshot)]
 pub struct CPU6502 {
     vss: In<1, 21>,
     rdy: In<2>,
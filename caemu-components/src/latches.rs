@@ -1,11 +1,12 @@
 use caemu::component::{Component, In, InBus, OutBus};
 use caemu::bus::{Bus, Signal};
 use caemu::delay::Delay;
-use caemu_macro::comp;
+use caemu_macro::{comp, Snapshot};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
+#[derive(Snapshot)]
 struct State {
     mem: [Signal; 4]
 }
@@ -18,7 +19,7 @@ impl State {
     }
 }
 
-#[comp]
+#[comp(snapshot)]
 pub struct SN74LS77 {
     d: In<1, 2, 5, 6>,
     c: In<12, 3>,
@@ -57,13 +58,15 @@ impl Component for SN74LS77 {
 
 #[cfg(test)]
 mod tests {
+    use caemu::component::Connect;
+    use caemu::snapshot::Snapshot;
     use caemu::tester::Tester;
     use crate::latches::*;
 
     #[test]
     fn latch_test() {
         // wrap a latch
-        let (mut tester, mut board) = Tester::from(&[12, 1], &[14], SN74LS77::new(), 14);
+        let (mut tester, mut board) = Tester::from(&[12, 1], &[14], SN74LS77::new(), 14).unwrap();
 
         // test few cases
         tester.test(&mut board, vec![Signal::ONE, Signal::ZERO], vec![Signal::ZERO]);
@@ -72,4 +75,29 @@ mod tests {
         tester.test(&mut board, vec![Signal::ZERO, Signal::ONE], vec![Signal::ONE]);
         tester.test(&mut board, vec![Signal::ZERO, Signal::ZERO], vec![Signal::ONE]);
     }
+
+    #[test]
+    fn pin_groups_test() {
+        // supply and unconnected pins are left out
+        let groups = SN74LS77::new().borrow().pin_groups();
+        let names : Vec<&str> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["d", "c", "q"]);
+        assert_eq!(groups[2].1, vec![14, 13, 9, 8]);
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let (mut tester, mut board) = Tester::from(&[12, 1], &[14], SN74LS77::new(), 14).unwrap();
+        tester.test(&mut board, vec![Signal::ONE, Signal::ONE], vec![Signal::ONE]);
+        tester.test(&mut board, vec![Signal::ZERO, Signal::ZERO], vec![Signal::ONE]);
+        let mut saved = Vec::new();
+        board.save(&mut saved);
+
+        tester.test(&mut board, vec![Signal::ONE, Signal::ZERO], vec![Signal::ZERO]);
+        tester.test(&mut board, vec![Signal::ZERO, Signal::ZERO], vec![Signal::ZERO]);
+
+        // the latched value comes back with the board
+        board.restore(&mut &saved[..]).unwrap();
+        tester.test(&mut board, vec![Signal::ZERO, Signal::ZERO], vec![Signal::ONE]);
+    }
 }
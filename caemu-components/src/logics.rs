@@ -10,6 +10,8 @@ use std::cell::RefCell;
 pub struct SN74LS00N {
     a: In<1, 4, 10, 13>,
     b: In<2, 5, 9, 12>,
+    // typical tPLH and tPHL of the TI SN74LS00 datasheet, VCC = 5 V, CL = 15 pF
+    #[delay(rise = "9ns", fall = "10ns")]
     y: Out<3, 6, 8, 11>,
     gnd: In<7>,
     vcc: In<14>
@@ -17,9 +19,9 @@ pub struct SN74LS00N {
 
 impl Component for SN74LS00N {
     fn eval(&mut self) -> Delay {
-        let a = self.a.get_u8();
-        let b = self.b.get_u8();
-        self.y.set_u8(!(a&b));
+        for i in 0..4 {
+            self.y.set(i, !(self.a.get(i) & self.b.get(i)));
+        }
         Delay::from_nanos(15)
     }
 }
@@ -27,6 +29,8 @@ impl Component for SN74LS00N {
 #[comp]
 pub struct SN74LS04N {
     a: In<1, 3, 5, 9, 11, 13>,
+    // typical tPLH and tPHL of the TI SN74LS04 datasheet, VCC = 5 V, CL = 15 pF
+    #[delay(rise = "9ns", fall = "10ns")]
     y: Out<2, 4, 6, 8, 10, 12>,
     gnd: In<7>,
     vcc: In<14>
@@ -34,9 +38,9 @@ pub struct SN74LS04N {
 
 impl Component for SN74LS04N {
     fn eval(&mut self) -> Delay {
-        let a = self.a.get_u8();
-        println!("a: {} not a: {}", a, !a);
-        self.y.set_u8(!a);
+        for i in 0..6 {
+            self.y.set(i, !self.a.get(i));
+        }
         Delay::from_nanos(22)
     }
 }
@@ -53,13 +57,10 @@ pub struct HC138 {
 impl Component for HC138 {
 
     fn eval(&mut self) -> Delay {
-        let enable = self.e.get_u8();
-        if enable != 4 {
-            self.y.set_u8(0);
-        } else {
-            let a = self.a.get_u8();
-            let y = 1 << a;
-            self.y.set_u8(y);
+        match (self.e.try_get_u8(), self.a.try_get_u8()) {
+            (Some(4), Some(a)) => self.y.set_u8(1 << a),
+            (Some(4), None) | (None, _) => self.y.set_unknown(),
+            _ => self.y.set_u8(0)
         }
         Delay::from_nanos(53)
     }
@@ -68,29 +69,162 @@ impl Component for HC138 {
 
 #[cfg(test)]
 mod tests {
-    use caemu::tester::Tester;
+    use caemu::tester::{Tester, ProbeInput, ProbeOutput};
+    use caemu::supply::{Vcc, Gnd};
+    use caemu::board::{Board, Violation};
+    use caemu::error::Error;
+    use caemu::component::Connect;
     use caemu::bus::Signal;
+    use caemu::delay::Delay;
     use crate::logics::*;
 
     #[test]
     fn nand_test() {
         // wrap a nand
-        let (mut tester, mut board) = Tester::from(&[1, 2], &[3], SN74LS00N::new(), 14);
+        let (mut tester, mut board) = Tester::from(&[1, 2], &[3], SN74LS00N::new(), 14).unwrap();
 
         // test few cases
         tester.test(&mut board, vec![Signal::ZERO, Signal::ZERO], vec![Signal::ONE]);
         tester.test(&mut board, vec![Signal::ZERO, Signal::ONE], vec![Signal::ONE]);
         tester.test(&mut board, vec![Signal::ONE, Signal::ZERO], vec![Signal::ONE]);
         tester.test(&mut board, vec![Signal::ONE, Signal::ONE], vec![Signal::ZERO]);
+
+        // unknown propagates unless the other input forces the output
+        tester.test(&mut board, vec![Signal::ZERO, Signal::HIGH], vec![Signal::ONE]);
+        tester.test(&mut board, vec![Signal::ONE, Signal::HIGH], vec![Signal::UNKNOWN]);
+    }
+
+    #[test]
+    #[should_panic(expected = "output 0 is ZERO at 30000 ps, expected ONE\ntime       0 ps")]
+    fn failure_test() {
+        let (mut tester, mut board) = Tester::from(&[1, 2], &[3], SN74LS00N::new(), 14).unwrap();
+        tester.test(&mut board, vec![Signal::ONE, Signal::ZERO], vec![Signal::ONE]);
+        tester.test(&mut board, vec![Signal::ONE, Signal::ONE], vec![Signal::ONE]);
+    }
+
+    #[test]
+    fn unknown_pin_test() {
+        let nand = SN74LS00N::new();
+        assert_eq!(nand.borrow().get_name(14), Ok(String::from("vcc")));
+        assert_eq!(nand.borrow().get_name(15), Err(Error::UnknownPin{component: String::from("SN74LS00N"), pin: 15}));
+    }
+
+    #[test]
+    fn drc_test() {
+        let (_, board) = Tester::from(&[1, 2], &[3], SN74LS00N::new(), 14).unwrap();
+        let drc = board.drc();
+        assert!(drc.contains(&Violation::NoSupply(String::from("U4.vcc"))));
+        assert!(drc.contains(&Violation::NoSupply(String::from("U4.gnd"))));
+        assert!(drc.contains(&Violation::FloatingInput(String::from("U4.a1"))));
+        assert!(drc.contains(&Violation::UnusedOutput(String::from("U4.y1"))));
+        assert!(!drc.contains(&Violation::FloatingInput(String::from("U4.a0"))));
+    }
+
+    #[test]
+    fn power_on_test() {
+        let mut board = Board::new();
+        let socket_vcc = board.socket(1);
+        let socket_gnd = board.socket(1);
+        let socket_input = board.socket(1);
+        let socket_output = board.socket(1);
+        let socket_not = board.socket(14);
+        socket_vcc.pin(1).unwrap().connect(&socket_not.pin(14).unwrap());
+        socket_gnd.pin(1).unwrap().connect(&socket_not.pin(7).unwrap());
+        socket_input.pin(1).unwrap().connect(&socket_not.pin(1).unwrap());
+        socket_output.pin(1).unwrap().connect(&socket_not.pin(2).unwrap());
+        let mut board = board.wire();
+
+        let vcc = Vcc::new();
+        vcc.borrow_mut().set_power_on(Delay::from_nanos(100));
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(vcc).into(socket_vcc).unwrap();
+        board.plug(Gnd::new()).into(socket_gnd).unwrap();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        board.plug(SN74LS04N::new()).into(socket_not).unwrap();
+        let mut board = board.complete();
+        assert!(!board.drc().iter().any(|v| matches!(v, Violation::NoSupply(_))));
+
+        // outputs released until the rail comes up, then driven after the eval delay
+        // as they leave high impedance
+        input.borrow_mut().set(Signal::ZERO);
+        board.run_until(Delay::from_nanos(50)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::HIGH);
+        board.run_until(Delay::from_nanos(121)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::HIGH);
+        board.run_until(Delay::from_nanos(123)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+
+        // from then on, tPHL and tPLH
+        input.borrow_mut().set(Signal::ONE);
+        board.run_for(Delay::from_picos(9900)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+        board.run_for(Delay::from_picos(200)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+    }
+
+    #[test]
+    fn nand_delay_test() {
+        let (tester, mut board) = Tester::from(&[1, 2], &[3], SN74LS00N::new(), 14).unwrap();
+        tester.input(0).borrow_mut().set(Signal::ONE);
+        tester.input(1).borrow_mut().set(Signal::ONE);
+        board.eval().unwrap();
+        assert_eq!(tester.output(0).borrow().get(), Signal::ZERO);
+
+        // low to high: tPLH
+        tester.input(0).borrow_mut().set(Signal::ZERO);
+        board.run_for(Delay::from_picos(8900)).unwrap();
+        assert_eq!(tester.output(0).borrow().get(), Signal::ZERO);
+        board.run_for(Delay::from_picos(200)).unwrap();
+        assert_eq!(tester.output(0).borrow().get(), Signal::ONE);
+
+        // high to low: tPHL
+        tester.input(0).borrow_mut().set(Signal::ONE);
+        board.run_for(Delay::from_picos(9900)).unwrap();
+        assert_eq!(tester.output(0).borrow().get(), Signal::ONE);
+        board.run_for(Delay::from_picos(200)).unwrap();
+        assert_eq!(tester.output(0).borrow().get(), Signal::ZERO);
     }
 
     #[test]
     fn not_test() {
         // wrap a nand
-        let (mut tester, mut board) = Tester::from(&[1], &[2], SN74LS04N::new(), 14);
+        let (mut tester, mut board) = Tester::from(&[1], &[2], SN74LS04N::new(), 14).unwrap();
 
         // test few cases
         tester.test(&mut board, vec![Signal::ZERO], vec![Signal::ONE]);
         tester.test(&mut board, vec![Signal::ONE], vec![Signal::ZERO]);
     }
+
+    #[test]
+    fn ring_oscillator_test() {
+        // nand with its output back on one input: oscillates once enabled
+        let mut board = Board::new();
+        let socket_enable = board.socket(1);
+        let socket_nand = board.socket(14);
+        socket_enable.pin(1).unwrap().connect(&socket_nand.pin(1).unwrap());
+        socket_nand.pin(3).unwrap().connect(&socket_nand.pin(2).unwrap());
+        let mut board = board.wire();
+
+        let enable = ProbeInput::new();
+        board.plug(enable.clone()).into(socket_enable).unwrap();
+        board.plug(SN74LS00N::new()).into(socket_nand).unwrap();
+        let mut board = board.complete();
+        board.set_max_toggles(100);
+
+        enable.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+
+        enable.borrow_mut().set(Signal::ONE);
+        let oscillation = match board.eval() {
+            Err(Error::Oscillation(oscillation)) => oscillation,
+            other => panic!("expected an oscillation, got {:?}", other)
+        };
+        assert_eq!(oscillation.nets.len(), 1);
+        assert_eq!(oscillation.components, vec!["U2"]);
+
+        // the same while waiting for a condition that never holds
+        assert!(matches!(board.run_until(|_: &[Signal]| false), Err(Error::Oscillation(_))));
+    }
 }
@@ -1,11 +1,12 @@
 use caemu::component::{Component, In, InBus, InOutBus, OutBus};
 use caemu::bus::{Bus, Signal};
 use caemu::delay::Delay;
-use caemu_macro::comp;
+use caemu_macro::{comp, Snapshot};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
+#[derive(Snapshot)]
 pub struct StateRom {
     pub mem: [u8; 1 << 15]
 }
@@ -28,9 +29,10 @@ impl StateRom {
 }
 
 // EEPROM 32k
-#[comp]
+#[comp(snapshot)]
 pub struct AT28C256 {
     a: In<10, 9, 8, 7, 6, 5, 4, 3, 25, 24, 21, 23, 2, 26, 1>,
+    #[tristate]
     o: Out<11, 12, 13, 15, 16, 17, 18, 19>,
     gnd: In<14>,
     ce: In<20>,
@@ -38,23 +40,50 @@ pub struct AT28C256 {
     we: In<27>,
     vcc: In<28>,
 
-    pub state: StateRom
+    pub state: StateRom,
+    access: Access
+}
+
+// address read by the last eval, to know whether it just changed
+#[derive(Snapshot)]
+struct Access {
+    addr: Option<u16>
+}
+
+impl Access {
+    fn new() -> Self {
+        Access {addr: None}
+    }
 }
 
 impl Component for AT28C256 {
 
     fn eval(&mut self) -> Delay {
-        if self.ce.get() == Signal::ONE || self.oe.get() == Signal::ONE {
+        let ce = self.ce.get();
+        let oe = self.oe.get();
+        let addr = self.a.try_get_u16();
+        // data is valid tACC after the address changes, or tOE after the output is
+        // enabled on an address that was already stable
+        let delay = if addr.is_some() && addr == self.access.addr {
+            Delay::from_nanos(70)
+        } else {
+            Delay::from_nanos(150)
+        };
+        self.access.addr = addr;
+        if ce == Signal::ONE || oe == Signal::ONE {
             self.o.set_high();
-            return Delay::from_nanos(20);
+            Delay::from_nanos(20)
         } else {
-            let addr = self.a.get_u16() as usize;
-            self.o.set_u8(self.state.mem[addr]);
-            return Delay::from_nanos(150);
+            match addr {
+                Some(addr) if ce == Signal::ZERO && oe == Signal::ZERO => self.o.set_u8(self.state.mem[addr as usize]),
+                _ => self.o.set_unknown()
+            }
+            delay
         }
     }
 }
 
+#[derive(Snapshot)]
 struct State {
     mem: [u8; 1 << 15]
 }
@@ -68,7 +97,7 @@ impl State {
 }
 
 // static ram 32k
-#[comp]
+#[comp(snapshot)]
 pub struct AS6C62256 {
     a: In<10, 9, 8, 7, 6, 5, 4, 3, 25, 24, 21, 23, 2, 26, 1>,
     d: InOut<11, 12, 13, 15, 16, 17, 18, 19>,
@@ -93,13 +122,60 @@ impl Component for AS6C62256 {
             self.state.mem[addr] = self.d.get_u8();
         }
 
-        if self.oe.get() == Signal::ONE {
+        let oe = self.oe.get();
+        if oe == Signal::ONE {
             self.d.set_high();
-            return Delay::from_nanos(20);
+            Delay::from_nanos(20)
         } else {
-            let addr = self.a.get_u16() as usize;
-            self.d.set_u8(self.state.mem[addr]);
-            return Delay::from_nanos(55);
+            match self.a.try_get_u16() {
+                Some(addr) if oe == Signal::ZERO => self.d.set_u8(self.state.mem[addr as usize]),
+                _ => self.d.set_unknown()
+            }
+            Delay::from_nanos(55)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use caemu::board::Board;
+    use caemu::bus::Signal;
+    use caemu::delay::Delay;
+    use caemu::logger::Log;
+    use caemu::tester::ProbeInput;
+    use caemu::vcd::Recording;
+    use crate::memory::*;
+
+    #[test]
+    fn address_bus_test() {
+        let mut board = Board::new();
+        let socket_rom = board.socket(28);
+        let a = [10, 9, 8, 7, 6, 5, 4, 3, 25, 24, 21, 23, 2, 26, 1];
+        let sockets : Vec<_> = a.iter().map(|pin| {
+            let socket = board.socket(1);
+            socket.pin(1).unwrap().connect(&socket_rom.pin(*pin).unwrap());
+            socket
+        }).collect();
+        let mut board = board.wire();
+        board.plug(AT28C256::new()).into(socket_rom).unwrap();
+        let probes : Vec<_> = sockets.into_iter().map(|socket| {
+            let probe = ProbeInput::new();
+            board.plug(probe.clone()).into(socket).unwrap();
+            probe
+        }).collect();
+        let recording = Recording::new();
+        let mut board = board.complete_with_log(Log::Writer(Box::new(recording.clone()))).unwrap();
+
+        // a0 to a14 from the least significant bit, logged from a14
+        for (i, probe) in probes.iter().enumerate() {
+            probe.borrow_mut().set(if 0x4006 & (1 << i) != 0 { Signal::ONE } else { Signal::ZERO });
         }
+        board.run_for(Delay::from_nanos(10)).unwrap();
+        let waveform = recording.waveform().unwrap();
+        let mut expected = vec![Signal::ZERO; 15];
+        expected[0] = Signal::ONE;
+        expected[12] = Signal::ONE;
+        expected[13] = Signal::ONE;
+        assert_eq!(waveform.value("caemu.U1.a", Delay::from_nanos(10)), Some(expected));
     }
-}
\ No newline at end of file
+}
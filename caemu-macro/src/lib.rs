@@ -4,20 +4,95 @@ extern crate proc_macro2;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::{quote};
-use syn;
 
 struct PinDesc {
     name: Ident,
     kind: Ident,
-    pins: Vec<syn::LitInt>
+    pins: Vec<syn::LitInt>,
+    delay: Option<(u64, u64)>,
+    tristate: bool
 }
 
 enum Member {
     Managed(PinDesc),
-    Unmanaged(syn::Field)
+    Unmanaged(Box<syn::Field>)
+}
+
+// "15ns" -> 15000 (picoseconds)
+fn parse_duration(lit: &syn::LitStr) -> u64 {
+    let value = lit.value();
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number : u64 = value[..split].parse().unwrap_or_else(|_| panic!("Invalid delay {}", value));
+    let factor = match value[split..].trim() {
+        "ps" => 1,
+        "ns" => 1_000,
+        "us" => 1_000_000,
+        "ms" => 1_000_000_000,
+        "s" => 1_000_000_000_000,
+        unit => panic!("Unknown delay unit {} in {}", unit, value)
+    };
+    number * factor
+}
+
+// #[delay("10ns")] or #[delay(rise = "15ns", fall = "9ns")]
+fn parse_delay(attrs: &[syn::Attribute]) -> Option<(u64, u64)> {
+    let attr = attrs.iter().find(|attr| attr.path.is_ident("delay"))?;
+    let mut rise = None;
+    let mut fall = None;
+    if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+        for nested in list.nested.iter() {
+            match nested {
+                syn::NestedMeta::Lit(syn::Lit::Str(both)) => {
+                    rise = Some(parse_duration(both));
+                    fall = rise;
+                },
+                syn::NestedMeta::Meta(syn::Meta::NameValue(named)) => {
+                    if let syn::Lit::Str(value) = &named.lit {
+                        if named.path.is_ident("rise") {
+                            rise = Some(parse_duration(value));
+                        } else if named.path.is_ident("fall") {
+                            fall = Some(parse_duration(value));
+                        }
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+    match (rise, fall) {
+        (Some(rise), Some(fall)) => Some((rise, fall)),
+        _ => panic!("delay needs both a rise and a fall delay")
+    }
+}
+
+// kind of the pins for the design rule check, power pins are known by their name
+fn pin_kind(desc: &PinDesc) -> &'static str {
+    match desc.name.to_string().as_str() {
+        "vcc" | "vdd" => "Power",
+        "gnd" | "vss" => "Ground",
+        "nc" => "NoConnect",
+        _ => match desc.kind.to_string().as_str() {
+            "In" | "InBus" => "Input",
+            "Out" | "OutBus" if desc.tristate => "TriState",
+            "Out" | "OutBus" => "Output",
+            _ => "InOut"
+        }
+    }
 }
 
 fn parse_member(field: &syn::Field) -> Member {
+    match parse_pins(field) {
+        Member::Managed(mut desc) => {
+            desc.delay = parse_delay(&field.attrs);
+            desc.tristate = field.attrs.iter().any(|attr| attr.path.is_ident("tristate"));
+            Member::Managed(desc)
+        },
+        unmanaged => unmanaged
+    }
+}
+
+fn parse_pins(field: &syn::Field) -> Member {
     if let Some(name) = &field.ident {
         if let syn::Type::Path(path) = &field.ty {
             if let Some(last_item) = path.path.segments.last() {
@@ -35,39 +110,39 @@ fn parse_member(field: &syn::Field) -> Member {
                 match last_item.ident.to_string().as_str() {
                     "In" => {
                         if pins.len() == 1 {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("In", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("In", Span::call_site()), delay: None, tristate: false})
                         } else {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InBus", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InBus", Span::call_site()), delay: None, tristate: false})
                         }
                     },
                     "Out" => {
                         if pins.len() == 1 {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("Out", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("Out", Span::call_site()), delay: None, tristate: false})
                         } else {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("OutBus", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("OutBus", Span::call_site()), delay: None, tristate: false})
                         }
                     },
                     "InOut" => {
                         if pins.len() == 1 {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InOut", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InOut", Span::call_site()), delay: None, tristate: false})
                         } else {
-                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InOutBus", Span::call_site())})
+                            Member::Managed(PinDesc{name: name.clone(), pins, kind: Ident::new("InOutBus", Span::call_site()), delay: None, tristate: false})
                         }
                     },
-                    _ => Member::Unmanaged(field.clone())
+                    _ => Member::Unmanaged(Box::new(field.clone()))
                 }
             } else {
-                Member::Unmanaged(field.clone())
+                Member::Unmanaged(Box::new(field.clone()))
             }
         } else {
-            Member::Unmanaged(field.clone())
+            Member::Unmanaged(Box::new(field.clone()))
         }
     } else {
-        Member::Unmanaged(field.clone())
+        Member::Unmanaged(Box::new(field.clone()))
     }
 }
 
-fn make_comp(ast: syn::ItemStruct) -> TokenStream {
+fn make_comp(ast: syn::ItemStruct, snapshot: bool) -> TokenStream {
     let struct_name = ast.ident;
     let mut descs : Vec<PinDesc> = Vec::new();
     let mut members : Vec<syn::Field> = Vec::new();
@@ -79,7 +154,7 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
                     descs.push(pin_desc);
                 },
                 Member::Unmanaged(member) => {
-                    members.push(member);
+                    members.push(*member);
                 }
             }
         }
@@ -92,8 +167,25 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
     let mut pins_create = Vec::new();
     let mut total_pin_count: usize = 0;
     let mut get_names = Vec::new();
+    let mut get_delays = Vec::new();
+    let mut pin_kinds = Vec::new();
+    let mut pin_groups = Vec::new();
 
     for desc in descs {
+        let pin_kind = Ident::new(pin_kind(&desc), Span::call_site());
+        for id in desc.pins.iter() {
+            pin_kinds.push(quote!(
+                #id => Some(caemu::component::PinKind::#pin_kind)
+            ));
+        }
+        // supply and unconnected pins are no bus, the others are listed from bit 0
+        if desc.pins.len() > 1 && matches!(pin_kind.to_string().as_str(), "Input" | "Output" | "TriState" | "InOut") {
+            let str_name = desc.name.to_string();
+            let ids = &desc.pins;
+            pin_groups.push(quote!(
+                (String::from(#str_name), vec![#(#ids),*])
+            ));
+        }
         let name = desc.name;
         let kind = desc.kind;
         pins.push(quote!(#name: #kind));
@@ -119,6 +211,15 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
                 ));
             }
         }
+        if let Some((rise, fall)) = desc.delay {
+            let pins = &desc.pins;
+            get_delays.push(quote!(
+                #(#pins)|* => Some(caemu::bus::PinDelay{
+                    rise: caemu::delay::Delay::from_picos(#rise),
+                    fall: caemu::delay::Delay::from_picos(#fall)
+                })
+            ));
+        }
         connects.push(quote!(
             self.#name.connect(bus.clone())
         ));
@@ -140,7 +241,43 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
         ));
     };
 
+    // the pins hold no state, the other members are saved in declaration order
+    let (snapshot, snapshot_impl) = if !snapshot || members.is_empty() {
+        (quote!(), quote!())
+    } else {
+        let (save, restore) = snapshot_fields(members.iter().map(|m| m.ident.clone().unwrap()));
+        (quote!(
+            fn snapshot(&mut self) -> Option<&mut dyn caemu::snapshot::Snapshot> {
+                Some(self)
+            }
+        ), snapshot_trait(&struct_name, save, restore))
+    };
+
+    let get_pin_groups = if pin_groups.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            fn pin_groups(&self) -> Vec<(String, Vec<usize>)> {
+                vec![#(#pin_groups),*]
+            }
+        )
+    };
+
     let name_pin = quote::format_ident!("{}Pin", struct_name);
+    let str_struct_name = struct_name.to_string();
+
+    let get_delay = if get_delays.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            fn get_delay(&self, id: usize) -> Option<caemu::bus::PinDelay> {
+                match id {
+                    #(#get_delays,)*
+                    _ => None
+                }
+            }
+        )
+    };
 
     let gen = quote! {
         pub struct #struct_name {
@@ -156,20 +293,41 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
             pub fn len(&self) -> usize {
                 #total_pin_count
             }
+
+            pub fn is_empty(&self) -> bool {
+                #total_pin_count == 0
+            }
         }
 
         impl caemu::component::Connect for #struct_name {
             fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
                 #(#connects;)*
             }
-            fn get_name(&self, id: usize) -> String {
-                String::from(match id {
+            fn get_name(&self, id: usize) -> caemu::error::Result<String> {
+                Ok(String::from(match id {
                     #(#get_names,)*
-                    _ => panic!("Unknown pin id {}", id)
-                })
+                    _ => return Err(caemu::error::Error::UnknownPin{
+                        component: String::from(#str_struct_name),
+                        pin: id
+                    })
+                }))
             }
+            fn pin_count(&self) -> Option<usize> {
+                Some(#total_pin_count)
+            }
+            fn pin_kind(&self, id: usize) -> Option<caemu::component::PinKind> {
+                match id {
+                    #(#pin_kinds,)*
+                    _ => None
+                }
+            }
+            #get_delay
+            #get_pin_groups
+            #snapshot
         }
 
+        #snapshot_impl
+
         impl #struct_name {
             pub fn new() -> Rc<RefCell<Self>> {
                 Rc::new(RefCell::new(Self {
@@ -187,11 +345,61 @@ fn make_comp(ast: syn::ItemStruct) -> TokenStream {
     gen.into()
 }
 
+// calls to save and restore for each field
+fn snapshot_fields<T: quote::ToTokens>(fields: impl Iterator<Item = T>) -> (Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>) {
+    fields.map(|field| (
+        quote!(caemu::snapshot::Snapshot::save(&self.#field, out)),
+        quote!(caemu::snapshot::Snapshot::restore(&mut self.#field, input)?)
+    )).unzip()
+}
+
+fn snapshot_trait(name: &Ident, save: Vec<proc_macro2::TokenStream>, restore: Vec<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+    quote!(
+        impl caemu::snapshot::Snapshot for #name {
+            #[allow(unused_variables)]
+            fn save(&self, out: &mut Vec<u8>) {
+                #(#save;)*
+            }
+
+            #[allow(unused_variables)]
+            fn restore(&mut self, input: &mut &[u8]) -> caemu::error::Result<()> {
+                #(#restore;)*
+                Ok(())
+            }
+        }
+    )
+}
+
+/// Implements `caemu::snapshot::Snapshot` for a struct, saving its fields in order
+#[proc_macro_derive(Snapshot)]
+pub fn derive_snapshot(item: TokenStream) -> TokenStream {
+    let ast : syn::DeriveInput = syn::parse(item).unwrap();
+    let fields = match ast.data {
+        syn::Data::Struct(data) => data.fields,
+        _ => panic!("Snapshot can only be derived for structs")
+    };
+    let (save, restore) = match fields {
+        syn::Fields::Named(named) => snapshot_fields(named.named.into_iter().map(|f| f.ident.unwrap())),
+        fields => snapshot_fields((0..fields.len()).map(syn::Index::from))
+    };
+    snapshot_trait(&ast.ident, save, restore).into()
+}
+
+/// Makes a component of a struct whose fields are pins (`In<1>`, `Out<2, 3>`...) or
+/// state. The pins of a bus go from the least significant bit, as in `a: In<10, 9, 8>`
+/// for a0 to a2: they are read, written and logged that way. With `#[comp(snapshot)]`,
+/// the state fields are saved with the board and must then implement
+/// `caemu::snapshot::Snapshot`.
 #[proc_macro_attribute]
-pub fn comp(_attrs: TokenStream, item: TokenStream) -> TokenStream {
+pub fn comp(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let snapshot = match attrs.to_string().trim() {
+        "" => false,
+        "snapshot" => true,
+        other => panic!("Unknown comp option {}", other)
+    };
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
     let ast = syn::parse(item).unwrap();
 
-    make_comp(ast)
+    make_comp(ast, snapshot)
 }
@@ -1,12 +1,16 @@
 use caemu::board::*;
 use caemu::delay::*;
+use caemu::clock::Clock;
+use caemu::supply::{Vcc, Gnd};
+use caemu::error::Result;
+use caemu::logger::Log;
 use caemu_components::logics::*;
 use caemu_components::memory::*;
 use caemu_components::io::Terminal;
 
 // Test board, using a 6809 CPU, RAM (data), ROM (code) and an output terminal
 
-fn main() {
+fn main() -> Result<()> {
     // create the board
     let mut board = Board::new();
 
@@ -22,6 +26,7 @@ fn main() {
     let demux_pins = demux.borrow().get_pins();
     let terminal = Terminal::new();
     let terminal_pins = terminal.borrow().get_pins();
+    let clock = Clock::new(1_000_000);
 
     // create the sockets on the board
     let socket_rom = board.socket(rom_pins.len());
@@ -29,31 +34,67 @@ fn main() {
     let socket_cpu = board.socket(cpu_pins.len());
     let socket_terminal = board.socket(terminal_pins.len());
     let socket_demux = board.socket(demux_pins.len());
+    let socket_clock = board.socket(1);
+    let socket_vcc = board.socket(1);
+    let socket_gnd = board.socket(1);
 
     // connections
-    socket_cpu.pins(&cpu_pins.a)
-        .connect(&socket_ram.pins(&ram_pins.a))
-        .connect(&socket_rom.pins(&rom_pins.a));
-    socket_cpu.pins(&cpu_pins.d)
-        .connect(&socket_ram.pins(&ram_pins.d))
-        .connect(&socket_rom.pins(&rom_pins.o));
+    // A15 is left to the address decoding, both memories are 32K
+    socket_cpu.pins(&cpu_pins.a[..15])?
+        .label("A")
+        .connect(&socket_ram.pins(&ram_pins.a)?)?
+        .connect(&socket_rom.pins(&rom_pins.a)?)?;
+    socket_cpu.pins(&cpu_pins.d)?
+        .label("D")
+        .connect(&socket_ram.pins(&ram_pins.d)?)?
+        .connect(&socket_rom.pins(&rom_pins.o)?)?;
+
+    socket_clock.pin(1)?.connect(&socket_cpu.pin(cpu_pins.phy[0])?);
+    socket_clock.pin(1)?.label("PHI0");
+
+    // supply rails
+    let vcc = socket_vcc.pin(1)?;
+    vcc.label("VCC");
+    vcc.connect(&socket_cpu.pin(cpu_pins.vcc[0])?);
+    vcc.connect(&socket_rom.pin(rom_pins.vcc[0])?);
+    vcc.connect(&socket_ram.pin(ram_pins.vcc[0])?);
+    vcc.connect(&socket_demux.pin(demux_pins.vcc[0])?);
+    let gnd = socket_gnd.pin(1)?;
+    gnd.label("GND");
+    gnd.connect(&socket_cpu.pin(cpu_pins.vss[0])?);
+    gnd.connect(&socket_cpu.pin(cpu_pins.vss[1])?);
+    gnd.connect(&socket_rom.pin(rom_pins.gnd[0])?);
+    gnd.connect(&socket_ram.pin(ram_pins.vss[0])?);
+    gnd.connect(&socket_demux.pin(demux_pins.gnd[0])?);
+
+    // open collector lines of the CPU
+    socket_cpu.pin(cpu_pins.irq[0])?.pull_up();
+    socket_cpu.pin(cpu_pins.nmi[0])?.pull_up();
+    socket_cpu.pin(cpu_pins.rst[0])?.pull_up();
 
     // Wire the board: no more connection allowed
     let mut board = board.wire();
 
     // plug components / probes onto sockets
-    board.plug(cpu).into(socket_cpu);
-    board.plug(ram).into(socket_ram);
-    board.plug(rom).into(socket_rom);
-    board.plug(demux).into(socket_demux);
-    board.plug(terminal).into(socket_terminal);
-
-    // complete the board, no plug allowed
-    let mut board = board.complete();
-    let mut time = Delay::no_delay();
-    for _ in 0..50 {
-        board.move_time(time);
-        board.eval();
-        time = time.plus(&Delay::from_micros(1));
+    board.plug(cpu).into(socket_cpu)?;
+    board.plug(ram).into(socket_ram)?;
+    board.plug(rom).into(socket_rom)?;
+    board.plug(demux).into(socket_demux)?;
+    board.plug(terminal).into(socket_terminal)?;
+    board.plug(clock).into(socket_clock)?;
+    board.plug(Vcc::new()).into(socket_vcc)?;
+    board.plug(Gnd::new()).into(socket_gnd)?;
+
+    // complete the board, no plug allowed. The terminal prints on the standard output,
+    // the log goes to a file.
+    let mut board = board.complete_with_log(Log::File("testboard.vcd".into()))?;
+    for violation in board.drc().iter().filter(|v| v.severity() == Severity::Error) {
+        eprintln!("design rule check: {:?}", violation);
+    }
+    board.run_for(Delay::from_micros(50))?;
+
+    for contention in board.contentions() {
+        eprintln!("bus contention at {} ps on {}: {}", contention.time.picoseconds, contention.net, contention.drivers.join(", "));
     }
-}
\ No newline at end of file
+    Ok(())
+}
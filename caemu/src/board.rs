@@ -1,30 +1,46 @@
 use std::rc::Rc;
-use std::cell::{RefCell};
+use std::cell::{Cell, RefCell};
 use crate::delay::Delay;
-use crate::component::{Component, Connect};
-use crate::bus::{Bus, Signal, IOAction};
-use crate::logger::Logger;
+use crate::component::{Component, Connect, PinKind};
+use crate::bus::{Bus, Driver, Nets, IOAction, Pull, Signal};
+use crate::logger::{Log, Logger, Scope, Sink, Stdout, Var};
+use crate::error::{Error, Result};
+use crate::subboard::Port;
+use crate::snapshot::{Snapshot, invalid, take};
 
 use petgraph::Graph;
 use petgraph::algo::tarjan_scc;
 use petgraph::prelude::*;
 
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
 
 struct Connection {
     from: usize,
     to: usize
 }
 
+/// Name given to the net of a pin, labels also connect the pins sharing them
+struct NetName {
+    pin: usize,
+    name: String,
+    label: bool
+}
+
 struct InternalSocket {
-    size: usize
+    size: usize,
+    name: RefCell<String>,
+    plugged: Cell<bool>
 }
 
 pub struct Board {
     sockets: Vec<Rc<InternalSocket>>,
     connections: Rc<RefCell<Vec<Connection>>>,
     current_count: usize,
-    names: Rc<RefCell<HashMap<usize, String>>>
+    names: Rc<RefCell<Vec<NetName>>>,
+    pulls: Rc<RefCell<Vec<(usize, Pull)>>>,
+    ports: Vec<(Socket, String)>
 }
 
 pub struct Socket {
@@ -32,31 +48,109 @@ pub struct Socket {
     component: Option<Rc<RefCell<dyn Component>>>,
     location: usize,
     connections: Rc<RefCell<Vec<Connection>>>,
-    names: Rc<RefCell<HashMap<usize, String>>>
+    names: Rc<RefCell<Vec<NetName>>>,
+    pulls: Rc<RefCell<Vec<(usize, Pull)>>>
 }
 
 pub struct Pin {
     id: usize,
     connections: Rc<RefCell<Vec<Connection>>>,
-    names: Rc<RefCell<HashMap<usize, String>>>
+    names: Rc<RefCell<Vec<NetName>>>,
+    pulls: Rc<RefCell<Vec<(usize, Pull)>>>
 }
 
 pub struct Pins {
     ids: Vec<usize>,
+    names: Rc<RefCell<Vec<NetName>>>,
     connections: Rc<RefCell<Vec<Connection>>>,
+    pulls: Rc<RefCell<Vec<(usize, Pull)>>>
 }
 
 struct WiredComponent {
     component: Rc<RefCell<dyn Component>>,
-    bus: Rc<RefCell<Bus>>
+    state: Rc<RefCell<dyn Connect>>,
+    bus: Rc<RefCell<Bus>>,
+    name: String,
+    pins: Vec<String>,
+    kinds: Vec<Option<PinKind>>,
+    groups: Vec<(String, Vec<usize>)>,
+    pending: Vec<Vec<(Delay, Signal)>>,
+    delay_mode: Option<DelayMode>
 }
 
 pub struct WiredBoard {
+    sockets: Vec<Rc<InternalSocket>>,
     components: Vec<WiredComponent>,
     id_to_wire: Vec<usize>,
-    all_signals: Rc<RefCell<Vec<Signal>>>,
-    raised: Rc<RefCell<Vec<bool>>>,
-    names: Rc<RefCell<HashMap<usize, String>>>
+    nets: Rc<RefCell<Nets>>,
+    woken: Rc<RefCell<Vec<usize>>>,
+    now: Rc<Cell<Delay>>,
+    names: HashMap<usize, String>,
+    net_ids: HashMap<String, usize>,
+    ports: Vec<(Rc<RefCell<Port>>, usize)>
+}
+
+/// How a new value on an output affects the ones still pending on it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DelayMode {
+    /// Every value reaches the net, however short the pulse
+    Transport,
+    /// Pulses shorter than the propagation delay are swallowed, like real gates do
+    Inertial
+}
+
+/// A pulse swallowed by an output in inertial delay mode
+#[derive(Debug, PartialEq)]
+pub struct Glitch {
+    /// When the swallowed value would have reached the net
+    pub time: Delay,
+    pub net: String,
+    pub driver: String
+}
+
+/// Two or more components driving different values on the same net
+#[derive(Debug, PartialEq)]
+pub struct Contention {
+    pub time: Delay,
+    pub net: String,
+    pub drivers: Vec<String>
+}
+
+/// Problem found by the design rule check, pins are named `socket.pin`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Violation {
+    /// A socket nothing got plugged into
+    EmptySocket(String),
+    /// A power or ground pin on a net without supply. A floating one doesn't
+    /// unpower its chip, which runs as if supplied.
+    NoSupply(String),
+    /// An input on a net that nothing drives or pulls
+    FloatingInput(String),
+    /// Several always driven outputs on the same net
+    MultipleDrivers { net: String, drivers: Vec<String> },
+    /// An output on a net that nothing reads
+    UnusedOutput(String)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error
+}
+
+impl Violation {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Violation::MultipleDrivers { .. } => Severity::Error,
+            _ => Severity::Warning
+        }
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
@@ -64,17 +158,30 @@ impl Board {
         Board{
             sockets: Vec::new(),
             connections: Rc::from(RefCell::from(Vec::new())),
-            names: Rc::from(RefCell::from(HashMap::new())),
+            names: Rc::from(RefCell::from(Vec::new())),
+            pulls: Rc::from(RefCell::from(Vec::new())),
+            ports: Vec::new(),
             current_count: 0}
     }
 
+    /// Makes the net of a pin a pin of the board once wrapped in a `SubBoard`.
+    /// These pins are numbered from 1 in the order they are exposed.
+    pub fn expose(&mut self, pin: &Pin, name: &str) {
+        let socket = self.socket(1);
+        socket.name("port");
+        self.connections.borrow_mut().push(Connection{from: socket.location, to: pin.id});
+        self.ports.push((socket, String::from(name)));
+    }
+
     pub fn socket(&mut self, size: usize) -> Socket {
-        let internal = Rc::new(InternalSocket{size});
+        let name = format!("U{}", self.sockets.len() + 1);
+        let internal = Rc::new(InternalSocket{size, name: RefCell::from(name), plugged: Cell::new(false)});
         self.sockets.push(internal.clone());
         let res = Socket{internal,
             component: Option::None,
             location: self.current_count,
             names: self.names.clone(),
+            pulls: self.pulls.clone(),
             connections: self.connections.clone()};
         self.current_count += size;
         res
@@ -83,13 +190,22 @@ impl Board {
     pub fn wire(self) -> WiredBoard {
         let mut graph : Graph<usize, (), Undirected> = Graph::with_capacity(self.current_count, self.connections.borrow().len());
         let mut all_nodes = Vec::new();
-        let mut names = HashMap::new();
         for i in 0..self.current_count {
             all_nodes.push(graph.add_node(i));
         }
 
         graph.extend_with_edges(self.connections.borrow().iter().map(|x| (all_nodes[x.from], all_nodes[x.to])));
 
+        // pins sharing a label are on the same net
+        let mut labels : HashMap<&str, usize> = HashMap::new();
+        let net_names = self.names.borrow();
+        for label in net_names.iter().filter(|n| n.label) {
+            match labels.get(label.name.as_str()) {
+                Some(first) => { graph.add_edge(all_nodes[*first], all_nodes[label.pin], ()); },
+                None => { labels.insert(&label.name, label.pin); }
+            }
+        }
+
         let connected = tarjan_scc(&graph);
 
         let mut id_to_wire = Vec::new();
@@ -100,139 +216,1116 @@ impl Board {
             for node in g {
                 let id = *graph.node_weight(*node).unwrap();
                 id_to_wire[id] = i;
-                match self.names.borrow().get(&id) {
-                    Some(name) => names.insert(i, name.clone()),
-                    None => None
-                };
             }
         }
 
-        let mut all_signals = Vec::new();
-        let mut raised = Vec::new();
-        for _ in 0..connected.len() {
-            all_signals.push(Signal::HIGH);
-            raised.push(false);
+        // a net shows the first name it was given, and can be found by any of them
+        let mut names = HashMap::new();
+        let mut net_ids = HashMap::new();
+        for name in net_names.iter() {
+            let net = id_to_wire[name.pin];
+            names.entry(net).or_insert_with(|| name.name.clone());
+            net_ids.entry(name.name.clone()).or_insert(net);
+        }
+        drop(net_names);
+
+        let mut nets = Nets::new(connected.len());
+        for (id, pull) in self.pulls.borrow().iter() {
+            nets.pull(id_to_wire[*id], *pull);
         }
-        WiredBoard{components: Vec::new(),
+
+        let mut board = WiredBoard{sockets: self.sockets,
+            components: Vec::new(),
             id_to_wire,
-            names: Rc::from(RefCell::from(names)),
-            raised: Rc::from(RefCell::from(raised)),
-            all_signals: Rc::from(RefCell::from(all_signals))}
+            names,
+            net_ids,
+            woken: Rc::from(RefCell::from(Vec::new())),
+            now: Rc::new(Cell::new(Delay::no_delay())),
+            nets: Rc::from(RefCell::from(nets)),
+            ports: Vec::new()};
+
+        // the inside end of each exposed pin
+        for (socket, name) in self.ports {
+            let port = Port::new(&name);
+            let component = board.components.len();
+            board.plug(port.clone()).attach(socket, vec![name]);
+            board.ports.push((port, component));
+        }
+        board
     }
 }
 
 pub struct BoardComponent<'a, T: Component + Connect + 'static> {
     component: Rc<RefCell<T>>,
-    board: &'a mut WiredBoard
+    board: &'a mut WiredBoard,
+    delay_mode: Option<DelayMode>
 }
 
 impl <'a, T: Component + Connect> BoardComponent<'a, T> {
-    pub fn into(self, mut socket: Socket) {
+    /// Overrides the delay mode of the board for this component
+    pub fn delay_mode(mut self, mode: DelayMode) -> Self {
+        self.delay_mode = Some(mode);
+        self
+    }
+
+    /// Plugs the component into a socket of the same size
+    pub fn into(self, socket: Socket) -> Result<()> {
+        if let Some(count) = self.component.borrow().pin_count() {
+            if count != socket.internal.size {
+                return Err(Error::SocketSize{socket: socket.internal.name.borrow().clone(),
+                    expected: socket.internal.size, actual: count});
+            }
+        }
+        let pins = (1..=socket.internal.size).map(|i| self.component.borrow().get_name(i)).collect::<Result<Vec<_>>>()?;
+        self.attach(socket, pins);
+        Ok(())
+    }
+
+    /// Plugs the component once its pins are known to fit the socket
+    fn attach(self, mut socket: Socket, pins: Vec<String>) {
         socket.component = Some(self.component.clone());
+        socket.internal.plugged.set(true);
         let mut inputs = Vec::new();
         let mut read = Vec::new();
         let mut output = Vec::new();
+        let mut delays = Vec::new();
+        let mut kinds = Vec::new();
         for i in 0..socket.internal.size {
             inputs.push(self.board.id_to_wire[socket.location + i]);
             read.push(IOAction::None);
             output.push(IOAction::None);
-            self.board.names.borrow_mut().insert(socket.location + i, self.component.borrow().get_name(i + 1));
-            socket.pin(i+1).name(&self.component.borrow().get_name(i + 1));
+            delays.push(self.component.borrow().get_delay(i + 1));
+            kinds.push(self.component.borrow().pin_kind(i + 1));
         }
         let bus = Rc::from(RefCell::from(Bus{ids: inputs, read: RefCell::from(read), output,
-            raised: self.board.raised.clone(),
-            all_signals: self.board.all_signals.clone()}));
+            output_delays: vec![None; socket.internal.size],
+            delays,
+            component: self.board.components.len(),
+            woken: self.board.woken.clone(),
+            now: self.board.now.clone(),
+            nets: self.board.nets.clone()}));
         self.component.borrow_mut().connect(bus.clone());
-        self.board.components.push(WiredComponent{component: self.component, bus})
+        let name = socket.internal.name.borrow().clone();
+        let pending = vec![Vec::new(); pins.len()];
+        let groups = self.component.borrow().pin_groups().into_iter()
+            .filter(|(_, ids)| ids.iter().all(|id| *id >= 1 && *id <= pins.len()))
+            .collect();
+        self.board.components.push(WiredComponent{component: self.component.clone(), state: self.component,
+            bus, name, pins, kinds, groups, pending, delay_mode: self.delay_mode});
     }
 }
 
 pub struct CompleteBoard {
     components: Vec<WiredComponent>,
-    all_signals: Rc<RefCell<Vec<Signal>>>,
-    raised: Rc<RefCell<Vec<bool>>>,
+    nets: Rc<RefCell<Nets>>,
+    fanout: Vec<Vec<usize>>,
+    woken: Rc<RefCell<Vec<usize>>>,
+    schedule: BTreeMap<Delay, Vec<Event>>,
+    names: HashMap<usize, String>,
+    net_ids: HashMap<String, usize>,
+    now: Rc<Cell<Delay>>,
+    delay_mode: DelayMode,
+    glitches: Option<Vec<Glitch>>,
+    contentions: Vec<Contention>,
+    drc: Vec<Violation>,
+    max_delta_cycles: usize,
+    max_toggles: usize,
+    toggles: Vec<usize>,
+    toggled: Vec<usize>,
+    failure: Option<Error>,
+    ports: Vec<(Rc<RefCell<Port>>, usize)>,
+    logger: Option<Box<dyn Sink>>,
+    history: Option<History>,
+    max_checkpoints: usize
+}
+
+/// Why a run of the simulation returned
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StopReason {
+    /// No more events are pending
+    Quiescent,
+    /// The requested time was reached
+    TimeReached,
+    /// The requested condition became true
+    Condition,
+    /// One event timestamp was processed
+    Stepped
+}
+
+/// A loop that never settles: the nets still toggling when a limit was hit, and
+/// the components driving them
+#[derive(Debug, PartialEq, Clone)]
+pub struct Oscillation {
+    pub time: Delay,
+    pub nets: Vec<String>,
+    pub components: Vec<String>
+}
+
+impl fmt::Display for Oscillation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "oscillation at {} ps on nets {} driven by {}", self.time.picoseconds,
+            self.nets.join(", "), self.components.join(", "))
+    }
+}
+
+impl std::error::Error for Oscillation {}
+
+/// When to stop a run: at a given time, or when a condition on the nets holds
+pub trait Until {
+    /// Time after which no event gets processed
+    fn deadline(&self) -> Option<Delay> {
+        None
+    }
+
+    /// Checked after each processed timestamp, with the value of every net
+    fn reached(&mut self, _signals: &[Signal]) -> bool {
+        false
+    }
+}
+
+impl Until for Delay {
+    fn deadline(&self) -> Option<Delay> {
+        Some(*self)
+    }
+}
+
+impl <F: FnMut(&[Signal]) -> bool> Until for F {
+    fn reached(&mut self, signals: &[Signal]) -> bool {
+        self(signals)
+    }
+}
+
+/// What happens to a component at a scheduled time
+enum Event {
+    /// One of its outputs is applied to its net
+    Drive(usize, usize, Signal),
+    /// It gets evaluated again, whatever its inputs
+    Wake(usize)
+}
+
+impl WiredComponent {
+    /// Pins of the component in the order of the first one of each group, a group
+    /// being a single vector
+    fn scope(&self, base: usize) -> Scope {
+        let ids = self.bus.borrow().ids.clone();
+        let mut vars = Vec::new();
+        let mut done = vec![false; ids.len()];
+        for pin in 0..ids.len() {
+            if done[pin] {
+                continue;
+            }
+            match self.groups.iter().find(|(_, group)| group.contains(&(pin + 1))) {
+                Some((name, group)) => {
+                    vars.push(Var{name: name.clone(), nets: group.iter().rev().map(|id| base + ids[id - 1]).collect()});
+                    for id in group.iter() {
+                        done[id - 1] = true;
+                    }
+                },
+                None => vars.push(Var{name: self.pins[pin].clone(), nets: vec![base + ids[pin]]})
+            }
+        }
+        Scope{name: self.name.clone(), vars, children: Vec::new()}
+    }
+}
+
+impl Event {
+    fn save(&self, out: &mut Vec<u8>) {
+        match self {
+            Event::Drive(component, pin, signal) => {
+                out.push(0);
+                component.save(out);
+                pin.save(out);
+                signal.save(out);
+            },
+            Event::Wake(component) => {
+                out.push(1);
+                component.save(out);
+            }
+        }
+    }
+
+    fn load(input: &mut &[u8]) -> Result<Self> {
+        let tag = take(input, 1)?[0];
+        let mut component = 0usize;
+        component.restore(input)?;
+        match tag {
+            0 => {
+                let (mut pin, mut signal) = (0usize, Signal::default());
+                pin.restore(input)?;
+                signal.restore(input)?;
+                Ok(Event::Drive(component, pin, signal))
+            },
+            1 => Ok(Event::Wake(component)),
+            _ => Err(invalid("bad event"))
+        }
+    }
+}
+
+/// Something done to the board from outside, replayed when going back in time
+enum Input {
+    /// A net forced, or released with None
+    Force(usize, Option<Signal>),
+    /// Components evaluated at the start of a run, with the state they had then
+    Flush(Vec<(usize, Vec<u8>)>)
+}
+
+/// An input, applied after `position` timestamps were processed
+struct Record {
+    position: usize,
     time: Delay,
-    logger: Logger
+    input: Input
 }
 
-fn get_empty_entry() -> Vec<usize> {
+/// Saved state of the board, with what was recorded so far
+struct Checkpoint {
+    position: usize,
+    records: usize,
+    time: Delay,
+    state: Vec<u8>
+}
+
+/// What it takes to go back in time: checkpoints, and the inputs since the first one
+struct History {
+    interval: Delay,
+    position: usize,
+    checkpoints: Vec<Checkpoint>,
+    records: Vec<Record>
+}
+
+/// Start of the snapshot files, followed by the format version
+const SNAPSHOT_MAGIC: &[u8] = b"caemu snapshot 1\n";
+
+struct Quiescence;
+
+impl Until for Quiescence {}
+
+fn net_name(names: &HashMap<usize, String>, net: usize) -> String {
+    match names.get(&net) {
+        Some(name) => name.clone(),
+        None => format!("B{}", net)
+    }
+}
+
+fn get_empty_entry() -> Vec<Event> {
     vec!()
 }
 
 impl CompleteBoard {
-    pub fn move_time(&mut self, time: Delay) {
-        self.time = time;
+    pub fn time(&self) -> Delay {
+        self.now.get()
     }
 
-    pub fn eval(&mut self) {
-        let mut current_time = self.time;
-        let mut schedule : BTreeMap<Delay, Vec<usize>> = BTreeMap::new();
+    /// Moves the current time forward, processing the events pending until then.
+    /// Woken up components are evaluated by the next run, at the new time. A time
+    /// already passed leaves the board where it is.
+    pub fn move_time(&mut self, mut time: Delay) -> Result<()> {
+        self.run_events(&mut time).map(|_| ())
+    }
 
-        for (i, c) in self.components.iter_mut().enumerate() {
-            c.bus.borrow_mut().clear();
-            let delay = c.component.borrow_mut().eval();
-            let output_time = current_time.plus(&delay);
-            let comp = schedule.entry(output_time).or_insert_with(get_empty_entry);
-            comp.push(i);
+    /// Evaluates the components woken up since the last call (all of them the first
+    /// time), then runs the simulation until no more events are pending. A component
+    /// isn't evaluated on every call, only when a net it read changed or when woken
+    /// with `Bus::wake` or by its own `wakeups`. Fails if a net changes more than the
+    /// toggle limit on the way, as the board never settles: this is always the case
+    /// with a free-running source such as a `Clock`, run those boards for a duration.
+    pub fn eval(&mut self) -> Result<()> {
+        self.flush_woken();
+        self.run_events(&mut Quiescence).map(|_| ())
+    }
+
+    /// Runs the simulation for the given duration, see run_until
+    pub fn run_for(&mut self, duration: Delay) -> Result<StopReason> {
+        let deadline = self.now.get().plus(&duration);
+        self.run_until(deadline)
+    }
+
+    /// Runs the simulation until a time (`Delay`) or until a condition on the nets
+    /// (`|signals: &[Signal]| ...`, indexed by net) holds. When running until a time,
+    /// the board ends at that time even if it became quiescent before. Fails if the
+    /// delta cycle limit is hit, as time would never move forward, and without a
+    /// deadline, if the toggle limit is hit as with `eval`.
+    pub fn run_until<U: Until>(&mut self, mut until: U) -> Result<StopReason> {
+        self.flush_woken();
+        self.run_events(&mut until)
+    }
+
+    /// Most delta cycles (evaluations without time moving) allowed at a single time,
+    /// 10000 by default
+    pub fn set_max_delta_cycles(&mut self, max: usize) {
+        self.max_delta_cycles = max;
+    }
+
+    /// Most changes of a single net allowed during a run without a deadline (`eval`,
+    /// `run_until` a condition), 100000 by default
+    pub fn set_max_toggles(&mut self, max: usize) {
+        self.max_toggles = max;
+    }
+
+    /// Processes the next event timestamp only. Fails if a sub-board failed then.
+    pub fn step(&mut self) -> Result<StopReason> {
+        self.flush_woken();
+        let reason = match self.next_event() {
+            Some(time) => {
+                self.process(time);
+                StopReason::Stepped
+            },
+            None => StopReason::Quiescent
+        };
+        match self.failure.take() {
+            Some(error) => Err(error),
+            None => Ok(reason)
+        }
+    }
+
+    /// Time of the next pending event, if any
+    pub fn next_event(&self) -> Option<Delay> {
+        self.schedule.keys().next().cloned()
+    }
+
+    fn flush_woken(&mut self) {
+        let woken : BTreeSet<usize> = self.woken.borrow_mut().drain(..).collect();
+        if self.history.is_some() && !woken.is_empty() {
+            // components are woken from outside after a change of their state, saved here
+            let states = woken.iter().map(|i| {
+                let mut state = Vec::new();
+                if let Some(snapshot) = self.components[*i].state.borrow_mut().snapshot() {
+                    snapshot.save(&mut state);
+                }
+                (*i, state)
+            }).collect();
+            self.record_input(Input::Flush(states));
+        }
+        for i in woken {
+            self.evaluate(i);
         }
+    }
 
+    /// Processes events until `until` says to stop. Nets changed by the delta cycles
+    /// of the current time are kept to name the loop, and without a deadline, the
+    /// changes of every net since the start of the run are counted.
+    fn run_events<U: Until>(&mut self, until: &mut U) -> Result<StopReason> {
+        let settle = until.deadline().is_none();
+        for net in self.toggled.drain(..) {
+            self.toggles[net] = 0;
+        }
+        let mut deltas = 0;
+        let mut delta_nets : BTreeSet<usize> = BTreeSet::new();
         loop {
-            match schedule.iter().next() {
-                Some(e) => {
-                    current_time = *e.0;
-                    for c in e.1 {
-                        let component = self.components.get_mut(*c).unwrap();
-                        component.bus.borrow_mut().apply();
+            // a component that failed, such as a board plugged into this one, stops the run
+            if let Some(error) = self.failure.take() {
+                return Err(error);
+            }
+            let next = self.next_event();
+            match (next, until.deadline()) {
+                (Some(time), Some(deadline)) if time > deadline => {
+                    if deadline > self.now.get() {
+                        self.now.set(deadline);
+                    }
+                    return Ok(StopReason::TimeReached);
+                },
+                (None, Some(deadline)) => {
+                    if deadline > self.now.get() {
+                        self.now.set(deadline);
+                    }
+                    return Ok(StopReason::Quiescent);
+                },
+                (None, None) => return Ok(StopReason::Quiescent),
+                (Some(time), _) => {
+                    if time != self.now.get() {
+                        deltas = 0;
+                        delta_nets.clear();
+                    }
+                    let changed = self.process(time);
+                    if let Some(error) = self.failure.take() {
+                        return Err(error);
+                    }
+                    delta_nets.extend(changed.iter());
+                    deltas += 1;
+                    if deltas > self.max_delta_cycles {
+                        return Err(Error::Oscillation(self.oscillation(delta_nets.into_iter().collect())));
+                    }
+                    for net in changed.into_iter().filter(|_| settle) {
+                        if self.toggles[net] == 0 {
+                            self.toggled.push(net);
+                        }
+                        self.toggles[net] += 1;
+                        if self.toggles[net] > self.max_toggles {
+                            let limit = self.max_toggles / 2;
+                            let mut nets : Vec<usize> = self.toggled.iter().cloned().filter(|n| self.toggles[*n] > limit).collect();
+                            nets.sort_unstable();
+                            return Err(Error::Oscillation(self.oscillation(nets)));
+                        }
                     }
                 }
-                None => break
+            }
+            if until.reached(self.nets.borrow().signals()) {
+                return Ok(StopReason::Condition);
+            }
+        }
+    }
+
+    fn oscillation(&self, nets: Vec<usize>) -> Oscillation {
+        let mut components : BTreeSet<usize> = BTreeSet::new();
+        for net in nets.iter() {
+            components.extend(self.nets.borrow().drivers(*net).iter().map(|d| d.component));
+        }
+        Oscillation {
+            time: self.now.get(),
+            nets: nets.iter().map(|net| net_name(&self.names, *net)).collect(),
+            components: components.iter().map(|c| self.components[*c].name.clone()).collect()
+        }
+    }
+
+    fn process(&mut self, time: Delay) -> Vec<usize> {
+        if let Some(history) = &self.history {
+            let last = history.checkpoints.last().map(|c| c.time).unwrap_or_default();
+            if time >= last.plus(&history.interval) {
+                self.checkpoint();
+            }
+        }
+        if let Some(history) = &mut self.history {
+            history.position += 1;
+        }
+        self.now.set(time);
+        let mut woken : BTreeSet<usize> = BTreeSet::new();
+        for event in self.schedule.remove(&time).unwrap_or_default() {
+            match event {
+                Event::Drive(c, pin, signal) => {
+                    self.components[c].pending[pin].retain(|p| p.0 != time);
+                    self.components[c].bus.borrow().drive(pin, signal, time);
+                },
+                Event::Wake(c) => {
+                    woken.insert(c);
+                }
+            }
+        }
+        self.collect_contentions();
+        // only the components reading a net that changed may need an update
+        let changed = self.nets.borrow_mut().take_changed();
+        let mut candidates : BTreeSet<usize> = BTreeSet::new();
+        for net in changed.iter() {
+            candidates.extend(self.fanout[*net].iter());
+        }
+        for i in candidates.union(&woken) {
+            if woken.contains(i) || self.components[*i].bus.borrow().is_dirty() {
+                self.evaluate(*i);
+            }
+        }
+        self.nets.borrow_mut().clear_raised(&changed);
+
+        // logged once evaluated, for the boards plugged as components to be up to date
+        if self.logger.is_some() {
+            let mut signals = Vec::new();
+            self.collect_signals(&mut signals);
+            if let Some(logger) = &mut self.logger {
+                logger.log(&signals, &time);
+            }
+        }
+        changed
+    }
+
+    /// Nets of this board, then a scope for each socket with the pins of its component,
+    /// the boards plugged as components giving theirs
+    pub(crate) fn scope(&self, name: &str) -> Scope {
+        self.scope_from(name, &mut 0)
+    }
+
+    /// Scope of the board whose nets are logged from `offset`, which is moved past
+    /// them and the ones of the boards plugged into it, as in `collect_signals`
+    fn scope_from(&self, name: &str, offset: &mut usize) -> Scope {
+        let base = *offset;
+        let len = self.nets.borrow().len();
+        *offset += len;
+        let mut children = Vec::new();
+        for (i, c) in self.components.iter().enumerate() {
+            if self.ports.iter().any(|(_, port)| *port == i) {
+                continue;
+            }
+            let scope = match c.component.borrow().board() {
+                Some(board) => board.scope_from(&c.name, offset),
+                None => c.scope(base)
             };
-            schedule.remove(&current_time);
-            self.logger.log(&self.all_signals.borrow(), &current_time);
-            
-            for (i, c) in self.components.iter_mut().enumerate() {
-                if c.bus.borrow().is_dirty() {
-                    let delay = c.component.borrow_mut().eval();
-                    let output_time = current_time.plus(&delay);
-                    schedule.entry(output_time).or_insert_with(get_empty_entry).push(i);
+            children.push(scope);
+        }
+        Scope {
+            name: String::from(name),
+            vars: (0..len).map(|net| Var{name: net_name(&self.names, net), nets: vec![base + net]}).collect(),
+            children
+        }
+    }
+
+    /// Values of the nets in the same order as their names in `scope`
+    fn collect_signals(&self, signals: &mut Vec<Signal>) {
+        signals.extend_from_slice(self.nets.borrow().signals());
+        for c in self.components.iter() {
+            if let Some(board) = c.component.borrow().board() {
+                board.collect_signals(signals);
+            }
+        }
+    }
+
+    fn evaluate(&mut self, i: usize) {
+        let c = &self.components[i];
+        c.bus.borrow_mut().clear();
+        // power pins are read first, so that the chip is evaluated again when they change.
+        // Floating ones don't count: only a rail actually down unpowers the chip.
+        let powered = c.kinds.iter().enumerate().all(|(pin, kind)| match kind {
+            Some(PinKind::Power) => c.bus.borrow().get(pin) != Signal::ZERO,
+            Some(PinKind::Ground) => c.bus.borrow().get(pin) != Signal::ONE,
+            _ => true
+        });
+        let (delay, wakeups) = if powered {
+            let delay = c.component.borrow_mut().eval();
+            if let Some(error) = c.component.borrow().error() {
+                self.failure.get_or_insert_with(|| error.clone());
+            }
+            (delay, c.component.borrow_mut().wakeups(self.now.get()))
+        } else {
+            // an unpowered chip releases all its outputs
+            let mut bus = c.bus.borrow_mut();
+            for (pin, kind) in c.kinds.iter().enumerate() {
+                if matches!(kind, Some(PinKind::Output) | Some(PinKind::TriState) | Some(PinKind::InOut)) {
+                    bus.set_after(pin, Signal::HIGH, Delay::no_delay());
                 }
             }
-            for i in self.raised.borrow_mut().iter_mut() {
-                *i = false;
+            // its timers keep going
+            (Delay::no_delay(), c.component.borrow_mut().wakeups(self.now.get()))
+        };
+        // the level an output leaves is the last one it has pending, if any
+        let previous = |pin: usize| c.pending[pin].last().map(|p| p.1).unwrap_or_else(|| c.bus.borrow().driving(pin));
+        let outputs = c.bus.borrow().outputs(delay, previous);
+        for (pin, signal, delay) in outputs {
+            let output_time = self.now.get().plus(&delay);
+            self.cancel(i, pin, output_time, signal);
+            self.components[i].pending[pin].push((output_time, signal));
+            self.schedule.entry(output_time).or_insert_with(get_empty_entry).push(Event::Drive(i, pin, signal));
+        }
+
+        for wake_time in wakeups {
+            if wake_time >= self.now.get() {
+                self.schedule.entry(wake_time).or_insert_with(get_empty_entry).push(Event::Wake(i));
+            }
+        }
+    }
+
+    /// A new value on an output replaces the ones it had pending for the same time
+    /// or later. In inertial mode, it also swallows the earlier ones that differ.
+    fn cancel(&mut self, component: usize, pin: usize, time: Delay, signal: Signal) {
+        let inertial = self.components[component].delay_mode.unwrap_or(self.delay_mode) == DelayMode::Inertial;
+        let wc = &mut self.components[component];
+        let (cancelled, kept) : (Vec<_>, Vec<_>) = wc.pending[pin].iter()
+            .partition(|(t, s)| *t >= time || (inertial && *s != signal));
+        wc.pending[pin] = kept;
+        if let Some(glitches) = &mut self.glitches {
+            for (t, _) in cancelled.iter().filter(|(t, _)| *t < time) {
+                glitches.push(Glitch{time: *t,
+                    net: net_name(&self.names, wc.bus.borrow().ids[pin]),
+                    driver: format!("{}.{}", wc.name, wc.pins[pin])});
+            }
+        }
+        for (t, _) in cancelled {
+            if let Some(events) = self.schedule.get_mut(&t) {
+                events.retain(|e| !matches!(e, Event::Drive(c, p, _) if *c == component && *p == pin));
+                if events.is_empty() {
+                    self.schedule.remove(&t);
+                }
             }
         }
-        self.time = current_time;
+    }
+
+    /// Delay mode of the components that don't have their own, transport by default
+    pub fn set_delay_mode(&mut self, mode: DelayMode) {
+        self.delay_mode = mode;
+    }
+
+    /// Starts or stops recording the pulses swallowed in inertial delay mode
+    pub fn report_glitches(&mut self, report: bool) {
+        self.glitches = if report { Some(Vec::new()) } else { None };
+    }
+
+    /// Pulses swallowed since glitches are reported
+    pub fn glitches(&self) -> &[Glitch] {
+        match &self.glitches {
+            Some(glitches) => glitches,
+            None => &[]
+        }
+    }
+
+    pub(crate) fn ports_len(&self) -> usize {
+        self.ports.len()
+    }
+
+    pub(crate) fn port_name(&self, index: usize) -> Option<String> {
+        self.ports.get(index).map(|(port, _)| String::from(port.borrow().name()))
+    }
+
+    /// Drives an exposed pin from outside the board
+    pub(crate) fn set_port(&self, index: usize, value: Signal) {
+        self.ports[index].0.borrow_mut().set(value);
+    }
+
+    /// What the board drives on an exposed pin, leaving out what comes from outside
+    pub(crate) fn port_value(&self, index: usize) -> Signal {
+        let component = self.ports[index].1;
+        let net = self.components[component].bus.borrow().ids[0];
+        self.nets.borrow().driven_without(net, Driver{component, pin: 0})
+    }
+
+    /// Net with the given name or label, or named after one of its pins when it has none
+    pub fn net_id(&self, name: &str) -> Option<usize> {
+        self.net_ids.get(name).cloned()
+    }
+
+    /// Current value of a net found by name
+    pub fn get_net(&self, name: &str) -> Result<Signal> {
+        match self.net_id(name) {
+            Some(net) => Ok(self.nets.borrow().get(net)),
+            None => Err(Error::UnknownNet{name: String::from(name)})
+        }
+    }
+
+    /// Overrides the value of a net whatever drives it, until released. The components
+    /// reading it are evaluated by the next run.
+    pub fn force_net(&mut self, name: &str, signal: Signal) -> Result<()> {
+        self.force(name, Some(signal))
+    }
+
+    /// Gives a forced net back to its drivers
+    pub fn release_net(&mut self, name: &str) -> Result<()> {
+        self.force(name, None)
+    }
+
+    fn force(&mut self, name: &str, signal: Option<Signal>) -> Result<()> {
+        let net = self.net_id(name).ok_or_else(|| Error::UnknownNet{name: String::from(name)})?;
+        self.record_input(Input::Force(net, signal));
+        self.apply_force(net, signal);
+        Ok(())
+    }
+
+    fn apply_force(&mut self, net: usize, signal: Option<Signal>) {
+        self.nets.borrow_mut().force(net, signal, self.now.get());
+        self.collect_contentions();
+        // as in process, only the components that read the old value, not the drivers
+        let readers : Vec<usize> = self.fanout[net].iter().cloned()
+            .filter(|i| self.components[*i].bus.borrow().is_dirty())
+            .collect();
+        self.woken.borrow_mut().extend(readers);
+    }
+
+    /// Starts recording a checkpoint every `interval` of simulated time, along with
+    /// what is done to the board from outside (probes set, nets forced...), so that
+    /// `rewind_to` and `step_back` can go back to any time since now. A shorter
+    /// interval takes more memory, and less time to go back.
+    pub fn record(&mut self, interval: Delay) {
+        self.history = Some(History{interval, position: 0, checkpoints: Vec::new(), records: Vec::new()});
+        self.checkpoint();
+    }
+
+    /// Most checkpoints kept while recording, 100 by default: the oldest one goes,
+    /// with the inputs before the next one, and the board can't go back that far
+    pub fn set_max_checkpoints(&mut self, max: usize) {
+        self.max_checkpoints = max.max(1);
+    }
+
+    /// Stops recording and forgets what was recorded
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    fn record_input(&mut self, input: Input) {
+        if let Some(history) = &mut self.history {
+            history.records.push(Record{position: history.position, time: self.now.get(), input});
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        let mut state = Vec::new();
+        self.save(&mut state);
+        if let Some(history) = &mut self.history {
+            history.checkpoints.push(Checkpoint{position: history.position, records: history.records.len(),
+                time: self.now.get(), state});
+            if history.checkpoints.len() > self.max_checkpoints {
+                let dropped = history.checkpoints.len() - self.max_checkpoints;
+                history.checkpoints.drain(..dropped);
+                let records = history.checkpoints[0].records;
+                history.records.drain(..records);
+                for checkpoint in history.checkpoints.iter_mut() {
+                    checkpoint.records -= records;
+                }
+            }
+        }
+    }
+
+    /// Goes back to the given time, as the board was after processing the events
+    /// until then and the inputs given at that time. Recording goes on from there,
+    /// what came after is forgotten. Times after the current one are ignored.
+    pub fn rewind_to(&mut self, time: Delay) -> Result<()> {
+        if time >= self.now.get() {
+            return Ok(());
+        }
+        let position = self.history.as_ref().map(|h| h.position).unwrap_or_default();
+        self.replay(position, time)?;
+        self.now.set(time);
+        Ok(())
+    }
+
+    /// Goes back to before the last processed event timestamp
+    pub fn step_back(&mut self) -> Result<()> {
+        match self.history.as_ref().map(|h| h.position) {
+            Some(position) if position > 0 => self.replay(position - 1, Delay::from_picos(u64::MAX)),
+            _ => Err(Error::NotRecorded{time: self.now.get()})
+        }
+    }
+
+    fn replay(&mut self, position: usize, until: Delay) -> Result<()> {
+        let mut history = self.history.take().ok_or(Error::NotRecorded{time: until})?;
+        // nothing gets logged twice
+        let logger = self.logger.take();
+        let result = self.replay_from(&mut history, position, until);
+        self.logger = logger;
+        self.history = Some(history);
+        result
+    }
+
+    /// Restores the last checkpoint before `position` and `until`, then simulates
+    /// again from there, applying the recorded inputs, up to one of them
+    fn replay_from(&mut self, history: &mut History, position: usize, until: Delay) -> Result<()> {
+        let index = history.checkpoints.iter().rposition(|c| c.position <= position && c.time <= until)
+            .ok_or(Error::NotRecorded{time: until})?;
+        history.checkpoints.truncate(index + 1);
+        let checkpoint = &history.checkpoints[index];
+        self.restore(&mut &checkpoint.state[..])?;
+        history.position = checkpoint.position;
+        let mut next = checkpoint.records;
+        loop {
+            while let Some(record) = history.records.get(next).filter(|r| r.position == history.position && r.time <= until) {
+                self.now.set(record.time);
+                match &record.input {
+                    Input::Force(net, signal) => self.apply_force(*net, *signal),
+                    Input::Flush(states) => {
+                        for (i, state) in states.iter() {
+                            if let Some(snapshot) = self.components[*i].state.borrow_mut().snapshot() {
+                                snapshot.restore(&mut &state[..])?;
+                            }
+                        }
+                        *self.woken.borrow_mut() = states.iter().map(|(i, _)| *i).collect();
+                        self.flush_woken();
+                    }
+                }
+                next += 1;
+            }
+            match self.next_event() {
+                Some(time) if history.position < position && time <= until => {
+                    self.process(time);
+                    history.position += 1;
+                },
+                _ => break
+            }
+        }
+        history.records.truncate(next);
+        Ok(())
+    }
+
+    /// Saves the state of the simulation to a file, see `Snapshot`
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut out = Vec::from(SNAPSHOT_MAGIC);
+        self.save(&mut out);
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+
+    /// Restores the state of the simulation from a file written by `save_to_file`
+    /// for the same board
+    pub fn restore_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let data = std::fs::read(path)?;
+        let mut input = &data[..];
+        if take(&mut input, SNAPSHOT_MAGIC.len()).ok() != Some(SNAPSHOT_MAGIC) {
+            return Err(invalid("not a caemu snapshot"));
+        }
+        self.restore(&mut input)?;
+        if !input.is_empty() {
+            return Err(invalid("trailing data"));
+        }
+        Ok(())
+    }
+
+    /// Why the log stopped, if it failed to write
+    pub fn log_error(&self) -> Option<&Error> {
+        self.logger.as_ref().and_then(|logger| logger.error())
+    }
+
+    /// Writes the end of the log and stops logging, the simulation can go on
+    pub fn close_log(&mut self) -> Result<()> {
+        match self.logger.take() {
+            Some(mut logger) => logger.close(),
+            None => Ok(())
+        }
+    }
+
+    /// Problems found by the design rule check when the board was completed
+    pub fn drc(&self) -> &[Violation] {
+        &self.drc
+    }
+
+    /// Every bus contention seen so far: the time it started, the net and the pins driving it
+    pub fn contentions(&self) -> &[Contention] {
+        &self.contentions
+    }
+
+    fn collect_contentions(&mut self) {
+        for (time, net, drivers) in self.nets.borrow_mut().take_contentions() {
+            self.contentions.push(Contention {
+                time,
+                net: net_name(&self.names, net),
+                drivers: drivers.iter().map(|driver| {
+                    let component = &self.components[driver.component];
+                    format!("{}.{}", component.name, component.pins[driver.pin])
+                }).collect()
+            });
+        }
+    }
+}
+
+/// The whole state of the simulation: time, nets, pending events and the state of
+/// every component. Settings (delay mode, limits...), reports (glitches, the log)
+/// and the recording for `rewind_to` are left out. A failed restore leaves the board in an unspecified state.
+impl Snapshot for CompleteBoard {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.components.len().save(out);
+        self.now.get().save(out);
+        self.nets.borrow().save(out);
+        self.woken.borrow().save(out);
+        self.schedule.len().save(out);
+        for (time, events) in self.schedule.iter() {
+            time.save(out);
+            events.len().save(out);
+            for event in events.iter() {
+                event.save(out);
+            }
+        }
+        for c in self.components.iter() {
+            c.bus.borrow().read.borrow().save(out);
+            c.pending.save(out);
+            // length prefixed, so that a component restores exactly what it saved
+            let mut state = Vec::new();
+            if let Some(snapshot) = c.state.borrow_mut().snapshot() {
+                snapshot.save(&mut state);
+            }
+            state.save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let mut len = 0usize;
+        len.restore(input)?;
+        if len != self.components.len() {
+            return Err(invalid("not the same number of components"));
+        }
+        let mut now = Delay::no_delay();
+        now.restore(input)?;
+        self.now.set(now);
+        self.nets.borrow_mut().restore(input)?;
+        if self.nets.borrow().all_drivers().any(|d| d.component >= len || d.pin >= self.components[d.component].pins.len()) {
+            return Err(invalid("no such component"));
+        }
+        self.woken.borrow_mut().restore(input)?;
+        if self.woken.borrow().iter().any(|c| *c >= len) {
+            return Err(invalid("no such component"));
+        }
+
+        self.schedule.clear();
+        let mut times = 0usize;
+        times.restore(input)?;
+        for _ in 0..times {
+            let (mut time, mut count) = (Delay::no_delay(), 0usize);
+            time.restore(input)?;
+            count.restore(input)?;
+            let mut events = Vec::new();
+            for _ in 0..count {
+                let event = Event::load(input)?;
+                match event {
+                    Event::Drive(c, pin, _) if c < len && pin < self.components[c].pins.len() => (),
+                    Event::Wake(c) if c < len => (),
+                    _ => return Err(invalid("no such component"))
+                }
+                events.push(event);
+            }
+            self.schedule.insert(time, events);
+        }
+
+        for c in self.components.iter_mut() {
+            c.bus.borrow().read.borrow_mut().restore(input)?;
+            c.pending.restore(input)?;
+            if c.pending.len() != c.pins.len() || c.bus.borrow().read.borrow().len() != c.pins.len() {
+                return Err(invalid("not the same pins"));
+            }
+            let mut state : Vec<u8> = Vec::new();
+            state.restore(input)?;
+            let mut state = &state[..];
+            if let Some(snapshot) = c.state.borrow_mut().snapshot() {
+                snapshot.restore(&mut state)?;
+            }
+            if !state.is_empty() {
+                return Err(invalid(&format!("state of {} doesn't match", c.name)));
+            }
+        }
+        // what was recorded leads to another state, recording starts again from this one
+        if let Some(interval) = self.history.as_ref().map(|h| h.interval) {
+            self.record(interval);
+        }
+        Ok(())
     }
 }
 
 impl WiredBoard {
     pub fn plug<'a, T: Component + Connect>(&'a mut self, component: Rc<RefCell<T>>) -> BoardComponent<'a, T> {
-        BoardComponent{component, board: self}
+        BoardComponent{component, board: self, delay_mode: None}
+    }
+
+    /// Design rule check of the board as plugged so far. Pins of unknown kind are
+    /// assumed to both read and drive their net.
+    pub fn check(&self) -> Vec<Violation> {
+        let names = self.net_names();
+        let mut violations : Vec<Violation> = self.sockets.iter()
+            .filter(|socket| !socket.plugged.get())
+            .map(|socket| Violation::EmptySocket(socket.name.borrow().clone()))
+            .collect();
+
+        // kind of every pin on each net
+        let nets = self.nets.borrow();
+        let mut net_pins : Vec<Vec<(String, Option<PinKind>)>> = vec![Vec::new(); nets.len()];
+        for c in self.components.iter() {
+            for (pin, net) in c.bus.borrow().ids.iter().enumerate() {
+                net_pins[*net].push((format!("{}.{}", c.name, c.pins[pin]), c.kinds[pin]));
+            }
+        }
+
+        let drives = |kind: &Option<PinKind>| matches!(kind,
+            None | Some(PinKind::Output) | Some(PinKind::TriState) | Some(PinKind::InOut) | Some(PinKind::Supply));
+        let reads = |kind: &Option<PinKind>| matches!(kind,
+            None | Some(PinKind::Input) | Some(PinKind::InOut) | Some(PinKind::Power) | Some(PinKind::Ground));
+        for (net, pins) in net_pins.iter().enumerate() {
+            let always_on : Vec<String> = pins.iter()
+                .filter(|(_, kind)| *kind == Some(PinKind::Output))
+                .map(|(name, _)| name.clone())
+                .collect();
+            if always_on.len() > 1 {
+                violations.push(Violation::MultipleDrivers{net: net_name(&names, net), drivers: always_on});
+            }
+            let supplied = pins.iter().any(|(_, kind)| *kind == Some(PinKind::Supply));
+            for (i, (name, kind)) in pins.iter().enumerate() {
+                let others = || pins.iter().enumerate().filter(move |(j, _)| *j != i).map(|(_, (_, kind))| kind);
+                match kind {
+                    Some(PinKind::Power) | Some(PinKind::Ground) if !supplied =>
+                        violations.push(Violation::NoSupply(name.clone())),
+                    Some(PinKind::Input) if !nets.pulled(net) && !others().any(drives) =>
+                        violations.push(Violation::FloatingInput(name.clone())),
+                    Some(PinKind::Output) | Some(PinKind::TriState) if !others().any(reads) =>
+                        violations.push(Violation::UnusedOutput(name.clone())),
+                    _ => ()
+                }
+            }
+        }
+        violations
     }
 
+    /// Names of the nets, named after their first plugged pin when not named on the board
+    fn net_names(&self) -> HashMap<usize, String> {
+        let mut names = self.names.clone();
+        for c in self.components.iter() {
+            for (pin, net) in c.bus.borrow().ids.iter().enumerate() {
+                names.entry(*net).or_insert_with(|| format!("{}.{}", c.name, c.pins[pin]));
+            }
+        }
+        names
+    }
+
+    /// Completes the board, logging to the standard output
     pub fn complete(self) -> CompleteBoard {
-        let len = self.all_signals.borrow().len();
+        let mut board = self.complete_unlogged();
+        board.logger = Some(Box::new(Logger::new(&board.scope("caemu"), Box::new(Stdout))));
+        board
+    }
+
+    /// Completes the board, logging wherever told to. Fails if the log file can't be created.
+    pub fn complete_with_log(self, log: Log) -> Result<CompleteBoard> {
+        let mut board = self.complete_unlogged();
+        board.logger = log.open(&board.scope("caemu"))?;
+        Ok(board)
+    }
+
+    pub(crate) fn complete_unlogged(self) -> CompleteBoard {
+        let drc = self.check();
+        let names = self.net_names();
+        let mut net_ids = self.net_ids;
+        for (net, name) in names.iter() {
+            net_ids.entry(name.clone()).or_insert(*net);
+        }
+        let len = self.nets.borrow().len();
+
+        // net to reader index, so that a change only wakes the components wired to it
+        let mut fanout : Vec<Vec<usize>> = vec![Vec::new(); len];
+        for (i, c) in self.components.iter().enumerate() {
+            for net in c.bus.borrow().ids.iter() {
+                if fanout[*net].last() != Some(&i) {
+                    fanout[*net].push(i);
+                }
+            }
+        }
+
+        // everything gets evaluated at first
+        self.woken.borrow_mut().extend(0..self.components.len());
+
         CompleteBoard {
             components: self.components,
-            all_signals: self.all_signals,
-            raised: self.raised,
-            time: Delay::no_delay(),
-            logger: Logger::new(len, self.names.borrow())
+            nets: self.nets,
+            fanout,
+            woken: self.woken,
+            schedule: BTreeMap::new(),
+            names,
+            net_ids,
+            now: self.now,
+            delay_mode: DelayMode::Transport,
+            glitches: None,
+            contentions: Vec::new(),
+            drc,
+            max_delta_cycles: 10_000,
+            max_toggles: 100_000,
+            toggles: vec![0; len],
+            toggled: Vec::new(),
+            failure: None,
+            ports: self.ports,
+            logger: None,
+            history: None,
+            max_checkpoints: 100
         }
     }
 }
 
 impl Socket {
-    pub fn pin(&self, pin: usize) -> Pin {
-        Pin {id: self.location + pin - 1, names: self.names.clone(), connections: self.connections.clone()}
+    pub fn name(&self, name: &str) {
+        self.internal.name.replace(String::from(name));
+    }
+
+    /// Pin of the socket, numbered from 1
+    pub fn pin(&self, pin: usize) -> Result<Pin> {
+        Ok(Pin {id: self.id(pin)?, names: self.names.clone(), pulls: self.pulls.clone(), connections: self.connections.clone()})
     }
 
-    pub fn pins(&self, pins: &[usize]) -> Pins {
+    /// Group of pins of the socket, to connect to a group of the same size
+    pub fn pins(&self, pins: &[usize]) -> Result<Pins> {
         let mut pins_vec = Vec::new();
         for i in pins {
-            pins_vec.push(self.location + i);
+            pins_vec.push(self.id(*i)?);
+        }
+        Ok(Pins {ids: pins_vec, names: self.names.clone(), pulls: self.pulls.clone(), connections: self.connections.clone()})
+    }
+
+    fn id(&self, pin: usize) -> Result<usize> {
+        if pin == 0 || pin > self.internal.size {
+            return Err(Error::NoSuchPin{socket: self.internal.name.borrow().clone(), pin, size: self.internal.size});
         }
-        Pins {ids: pins_vec, connections: self.connections.clone()}
+        Ok(self.location + pin - 1)
     }
 }
 
@@ -241,16 +1334,707 @@ impl Pin {
         self.connections.borrow_mut().push(Connection{from: self.id, to: other.id});
     }
 
+    /// Names the net of this pin, the first name given to a net is the one it shows
     pub fn name(&self, name: &str) {
-        self.names.borrow_mut().insert(self.id, String::from(name));
+        self.names.borrow_mut().push(NetName{pin: self.id, name: String::from(name), label: false});
+    }
+
+    /// Names the net of this pin and connects it to every pin with the same label
+    pub fn label(&self, label: &str) {
+        self.names.borrow_mut().push(NetName{pin: self.id, name: String::from(label), label: true});
+    }
+
+    /// Pulls the net of this pin to ONE while nothing drives it
+    pub fn pull_up(&self) {
+        self.pulls.borrow_mut().push((self.id, Pull::Up));
+    }
+
+    /// Pulls the net of this pin to ZERO while nothing drives it
+    pub fn pull_down(&self) {
+        self.pulls.borrow_mut().push((self.id, Pull::Down));
+    }
+
+    /// Makes the net of this pin hold its last driven value while nothing drives it
+    pub fn keeper(&self) {
+        self.pulls.borrow_mut().push((self.id, Pull::Keeper));
     }
 }
 
 impl Pins {
-    pub fn connect(&self, other: &Pins) -> &Self {
+    /// Connects each pin to the one at the same position in `other`
+    pub fn connect(&self, other: &Pins) -> Result<&Self> {
+        if self.ids.len() != other.ids.len() {
+            return Err(Error::PinCountMismatch{expected: self.ids.len(), actual: other.ids.len()});
+        }
         for (from, to) in self.ids.iter().zip(&other.ids) {
             self.connections.borrow_mut().push(Connection{from: *from, to: *to});
         }
+        Ok(self)
+    }
+
+    /// Labels each pin with the prefix followed by its position, `D0`, `D1`... for `D`
+    pub fn label(&self, prefix: &str) -> &Self {
+        for (i, id) in self.ids.iter().enumerate() {
+            self.names.borrow_mut().push(NetName{pin: *id, name: format!("{}{}", prefix, i), label: true});
+        }
+        self
+    }
+
+    /// Pulls every net of these pins to ONE while nothing drives them
+    pub fn pull_up(&self) -> &Self {
+        self.pull(Pull::Up)
+    }
+
+    /// Pulls every net of these pins to ZERO while nothing drives them
+    pub fn pull_down(&self) -> &Self {
+        self.pull(Pull::Down)
+    }
+
+    /// Makes every net of these pins hold its last driven value while nothing drives them
+    pub fn keeper(&self) -> &Self {
+        self.pull(Pull::Keeper)
+    }
+
+    fn pull(&self, pull: Pull) -> &Self {
+        for id in self.ids.iter() {
+            self.pulls.borrow_mut().push((*id, pull));
+        }
         self
     }  
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::board::*;
+    use crate::component::{In, Out};
+    use crate::tester::{ProbeInput, ProbeOutput};
+    use crate::clock::Clock;
+
+    pub(crate) struct Buffer {
+        pub(crate) input: In,
+        pub(crate) output: Out
+    }
+
+    impl Component for Buffer {
+        fn eval(&mut self) -> Delay {
+            self.output.set(self.input.get());
+            Delay::from_nanos(10)
+        }
+    }
+
+    impl Connect for Buffer {
+        fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+            self.input.connect(bus.clone());
+            self.output.connect(bus);
+        }
+
+        fn get_name(&self, id: usize) -> Result<String> {
+            Ok(format!("p{}", id))
+        }
+    }
+
+    /// Zero delay inverter, unknown inputs read as ZERO
+    struct Inverter {
+        input: In,
+        output: Out
+    }
+
+    impl Component for Inverter {
+        fn eval(&mut self) -> Delay {
+            self.output.set(if self.input.get() == Signal::ONE { Signal::ZERO } else { Signal::ONE });
+            Delay::no_delay()
+        }
+    }
+
+    impl Connect for Inverter {
+        fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+            self.input.connect(bus.clone());
+            self.output.connect(bus);
+        }
+
+        fn get_name(&self, id: usize) -> Result<String> {
+            Ok(format!("p{}", id))
+        }
+    }
+
+    /// Zero delay nand, with its output on its second input: oscillates once enabled
+    pub(crate) struct Loop {
+        pub(crate) enable: In,
+        pub(crate) feedback: In,
+        pub(crate) output: Out
+    }
+
+    impl Component for Loop {
+        fn eval(&mut self) -> Delay {
+            self.output.set(!(self.enable.get() & self.feedback.get()));
+            Delay::no_delay()
+        }
+    }
+
+    impl Connect for Loop {
+        fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+            self.enable.connect(bus.clone());
+            self.feedback.connect(bus.clone());
+            self.output.connect(bus);
+        }
+
+        fn get_name(&self, id: usize) -> Result<String> {
+            Ok(format!("p{}", id))
+        }
+    }
+
+    /// Counts its evaluations
+    struct Counter {
+        input: In,
+        evals: Rc<Cell<usize>>
+    }
+
+    impl Component for Counter {
+        fn eval(&mut self) -> Delay {
+            self.input.get();
+            self.evals.set(self.evals.get() + 1);
+            Delay::no_delay()
+        }
+    }
+
+    impl Connect for Counter {
+        fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+            self.input.connect(bus);
+        }
+
+        fn get_name(&self, id: usize) -> Result<String> {
+            Ok(format!("p{}", id))
+        }
+    }
+
+    /// Toggles its output at fixed times, requested all at once
+    struct Pulses {
+        output: Out,
+        level: Signal,
+        requested: bool
+    }
+
+    impl Component for Pulses {
+        fn eval(&mut self) -> Delay {
+            self.level = !self.level;
+            self.output.set(self.level);
+            Delay::no_delay()
+        }
+
+        fn wakeups(&mut self, _now: Delay) -> Vec<Delay> {
+            if self.requested {
+                return Vec::new();
+            }
+            self.requested = true;
+            vec![Delay::from_nanos(70), Delay::from_nanos(30)]
+        }
+    }
+
+    impl Connect for Pulses {
+        fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+            self.output.connect(bus);
+        }
+
+        fn get_name(&self, id: usize) -> Result<String> {
+            Ok(format!("p{}", id))
+        }
+    }
+
+    #[test]
+    fn wakeups_test() {
+        let mut board = Board::new();
+        let socket_pulses = board.socket(1);
+        let socket_output = board.socket(1);
+        socket_pulses.pin(1).unwrap().connect(&socket_output.pin(1).unwrap());
+        let mut board = board.wire();
+
+        let output = ProbeOutput::new();
+        board.plug(Rc::new(RefCell::new(Pulses{output: Out::new(1), level: Signal::ZERO, requested: false}))).into(socket_pulses).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+
+        board.run_until(Delay::from_nanos(10)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+        board.run_until(Delay::from_nanos(50)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+        assert_eq!(board.next_event(), Some(Delay::from_nanos(70)));
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+        assert_eq!(board.time(), Delay::from_nanos(70));
+    }
+
+    #[test]
+    fn sensitivity_test() {
+        let mut board = Board::new();
+        let (socket_a, socket_b) = (board.socket(1), board.socket(1));
+        let (socket_reader_a, socket_reader_b) = (board.socket(1), board.socket(1));
+        socket_a.pin(1).unwrap().connect(&socket_reader_a.pin(1).unwrap());
+        socket_b.pin(1).unwrap().connect(&socket_reader_b.pin(1).unwrap());
+        let mut board = board.wire();
+
+        let (a, b) = (ProbeInput::new(), ProbeInput::new());
+        let (evals_a, evals_b) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        board.plug(a.clone()).into(socket_a).unwrap();
+        board.plug(b.clone()).into(socket_b).unwrap();
+        board.plug(Rc::new(RefCell::new(Counter{input: In::new(1), evals: evals_a.clone()}))).into(socket_reader_a).unwrap();
+        board.plug(Rc::new(RefCell::new(Counter{input: In::new(1), evals: evals_b.clone()}))).into(socket_reader_b).unwrap();
+        let mut board = board.complete();
+
+        // everything is evaluated the first time
+        board.eval().unwrap();
+        let (first_a, first_b) = (evals_a.get(), evals_b.get());
+        assert!(first_a > 0 && first_b > 0);
+
+        // then only the readers of the nets that changed
+        a.borrow_mut().set(Signal::ONE);
+        board.eval().unwrap();
+        assert_eq!(evals_a.get(), first_a + 1);
+        assert_eq!(evals_b.get(), first_b);
+
+        // the same value again changes no net
+        a.borrow_mut().set(Signal::ONE);
+        board.eval().unwrap();
+        assert_eq!(evals_a.get(), first_a + 1);
+    }
+
+    fn pulse(mode: DelayMode) -> Vec<Glitch> {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        let socket_output = board.socket(1);
+        socket_input.pin(1).unwrap().connect(&socket_buffer.pin(1).unwrap());
+        socket_buffer.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+        let mut board = board.wire();
+
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)})))
+            .delay_mode(mode)
+            .into(socket_buffer).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+        board.report_glitches(true);
+
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+
+        // 5 ns pulse through a 10 ns buffer
+        input.borrow_mut().set(Signal::ONE);
+        board.run_for(Delay::from_nanos(5)).unwrap();
+        input.borrow_mut().set(Signal::ZERO);
+        let pulse = board.run_until(|signals: &[Signal]| signals.contains(&Signal::ONE)).unwrap();
+        assert_eq!(pulse == StopReason::Condition, mode == DelayMode::Transport);
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+        board.glitches.take().unwrap()
+    }
+
+    #[test]
+    fn inertial_test() {
+        assert!(pulse(DelayMode::Transport).is_empty());
+        let glitches = pulse(DelayMode::Inertial);
+        assert_eq!(glitches.len(), 1);
+        assert_eq!(glitches[0].time, Delay::from_nanos(20));
+        assert_eq!(glitches[0].net, "U2.p2");
+        assert_eq!(glitches[0].driver, "U2.p2");
+    }
+
+    #[test]
+    fn run_test() {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        let socket_output = board.socket(1);
+        socket_input.pin(1).unwrap().connect(&socket_buffer.pin(1).unwrap());
+        socket_buffer.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+        let mut board = board.wire();
+
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_buffer).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+
+        input.borrow_mut().set(Signal::ONE);
+        assert_eq!(board.run_for(Delay::from_nanos(5)), Ok(StopReason::TimeReached));
+        assert_eq!(board.time(), Delay::from_nanos(5));
+        assert_eq!(output.borrow().get(), Signal::UNKNOWN);
+
+        assert_eq!(board.step(), Ok(StopReason::Stepped));
+        assert_eq!(board.time(), Delay::from_nanos(10));
+        assert_eq!(output.borrow().get(), Signal::ONE);
+
+        input.borrow_mut().set(Signal::ZERO);
+        let reason = board.run_until(|signals: &[Signal]| !signals.contains(&Signal::ONE));
+        assert_eq!(reason, Ok(StopReason::Condition));
+        assert_eq!(board.time(), Delay::from_nanos(20));
+
+        assert_eq!(board.run_until(Delay::from_nanos(100)), Ok(StopReason::Quiescent));
+        assert_eq!(board.time(), Delay::from_nanos(100));
+        assert_eq!(board.step(), Ok(StopReason::Quiescent));
+
+        // a deadline already passed never moves time backwards
+        input.borrow_mut().set(Signal::ONE);
+        assert_eq!(board.run_until(Delay::from_nanos(50)), Ok(StopReason::TimeReached));
+        assert_eq!(board.time(), Delay::from_nanos(100));
+        board.move_time(Delay::from_nanos(50)).unwrap();
+        assert_eq!(board.time(), Delay::from_nanos(100));
+    }
+
+    #[test]
+    fn delta_loop_test() {
+        let mut board = Board::new();
+        let socket = board.socket(2);
+        socket.pin(2).unwrap().connect(&socket.pin(1).unwrap());
+        let mut board = board.wire();
+        board.plug(Rc::new(RefCell::new(Inverter{input: In::new(1), output: Out::new(2)}))).into(socket).unwrap();
+        let mut board = board.complete();
+        board.set_max_delta_cycles(50);
+
+        // time never moves forward, even a bounded run has to give up
+        let oscillation = match board.run_for(Delay::from_nanos(10)) {
+            Err(Error::Oscillation(oscillation)) => oscillation,
+            other => panic!("expected an oscillation, got {:?}", other)
+        };
+        assert_eq!(oscillation.time, Delay::no_delay());
+        assert_eq!(oscillation.nets.len(), 1);
+        assert_eq!(oscillation.components, vec!["U1"]);
+    }
+
+    #[test]
+    fn wiring_errors_test() {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        socket_buffer.name("buffer");
+
+        assert_eq!(socket_buffer.pin(0).err(), Some(Error::NoSuchPin{socket: String::from("buffer"), pin: 0, size: 2}));
+        assert_eq!(socket_buffer.pins(&[1, 3]).err(), Some(Error::NoSuchPin{socket: String::from("buffer"), pin: 3, size: 2}));
+        let mismatch = socket_input.pins(&[1]).unwrap().connect(&socket_buffer.pins(&[1, 2]).unwrap()).err();
+        assert_eq!(mismatch, Some(Error::PinCountMismatch{expected: 1, actual: 2}));
+
+        let mut board = board.wire();
+        let error = board.plug(ProbeInput::new()).into(socket_buffer).err();
+        assert_eq!(error, Some(Error::SocketSize{socket: String::from("buffer"), expected: 2, actual: 1}));
+        assert_eq!(error.unwrap().to_string(), "socket buffer has 2 pins, the component plugged into it has 1");
+    }
+
+    #[test]
+    fn drc_test() {
+        let mut board = Board::new();
+        let socket_clock = board.socket(1);
+        let socket_other_clock = board.socket(1);
+        let socket_output = board.socket(1);
+        let socket_floating = board.socket(1);
+        let socket_pulled = board.socket(1);
+        let socket_unused = board.socket(1);
+        board.socket(1).name("spare");
+        socket_clock.pin(1).unwrap().connect(&socket_output.pin(1).unwrap());
+        socket_other_clock.pin(1).unwrap().connect(&socket_output.pin(1).unwrap());
+        socket_clock.pin(1).unwrap().name("clk");
+        socket_pulled.pin(1).unwrap().pull_up();
+        let mut board = board.wire();
+
+        board.plug(Clock::new(1_000_000)).into(socket_clock).unwrap();
+        board.plug(Clock::new(1_000_000)).into(socket_other_clock).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_output).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_floating).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_pulled).unwrap();
+        board.plug(ProbeInput::new()).into(socket_unused).unwrap();
+        let board = board.complete();
+
+        let drc = board.drc();
+        assert_eq!(drc.len(), 4);
+        assert!(drc.contains(&Violation::EmptySocket(String::from("spare"))));
+        assert!(drc.contains(&Violation::FloatingInput(String::from("U4.o1"))));
+        assert!(drc.contains(&Violation::UnusedOutput(String::from("U6.i1"))));
+        let multiple = drc.iter().find(|v| v.severity() == Severity::Error).unwrap();
+        match multiple {
+            Violation::MultipleDrivers { drivers, .. } => assert_eq!(drivers, &vec![String::from("U1.clk"), String::from("U2.clk")]),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn names_test() {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        let socket_output = board.socket(1);
+        // connected through their label only
+        socket_input.pin(1).unwrap().label("/CS");
+        socket_buffer.pin(1).unwrap().label("/CS");
+        socket_buffer.pin(1).unwrap().name("select");
+        socket_buffer.pins(&[2]).unwrap().label("D").connect(&socket_output.pins(&[1]).unwrap()).unwrap();
+        let mut board = board.wire();
+
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_buffer).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+        assert_eq!(board.net_id("/CS"), board.net_id("select"));
+        assert_eq!(board.get_net("D0"), Ok(Signal::ZERO));
+        let names : Vec<String> = board.scope("caemu").vars.into_iter().map(|var| var.name).collect();
+        assert_eq!(names, vec!["/CS", "D0"]);
+        assert_eq!(board.get_net("D1"), Err(Error::UnknownNet{name: String::from("D1")}));
+    }
+
+    #[test]
+    fn force_test() {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        let socket_output = board.socket(1);
+        socket_input.pin(1).unwrap().connect(&socket_buffer.pin(1).unwrap());
+        socket_input.pin(1).unwrap().name("in");
+        socket_buffer.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+        socket_buffer.pin(2).unwrap().name("out");
+        let mut board = board.wire();
+
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_buffer).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+        assert_eq!(board.get_net("out"), Ok(Signal::ZERO));
+
+        // forced over the probe driving it, then given back
+        board.force_net("in", Signal::ONE).unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ONE));
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+        board.release_net("in").unwrap();
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+
+        // forced over the buffer output, readers see it right away
+        board.force_net("out", Signal::UNKNOWN).unwrap();
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::UNKNOWN);
+        assert!(board.contentions().is_empty());
+        assert_eq!(board.force_net("nowhere", Signal::ONE), Err(Error::UnknownNet{name: String::from("nowhere")}));
+    }
+
+    #[test]
+    fn force_clock_test() {
+        // the clock net sampled every 100 ns, forced for a while or not
+        let samples = |force: bool| {
+            let mut board = Board::new();
+            let socket_clock = board.socket(1);
+            let socket_probe = board.socket(1);
+            socket_clock.pin(1).unwrap().connect(&socket_probe.pin(1).unwrap());
+            socket_clock.pin(1).unwrap().name("clk");
+            let mut board = board.wire();
+            let probe = ProbeOutput::new();
+            board.plug(Clock::new(1_000_000)).into(socket_clock).unwrap();
+            board.plug(probe.clone()).into(socket_probe).unwrap();
+            let mut board = board.complete_unlogged();
+
+            board.run_until(Delay::from_nanos(2250)).unwrap();
+            if force {
+                board.force_net("clk", Signal::ONE).unwrap();
+                board.run_for(Delay::from_nanos(100)).unwrap();
+                board.release_net("clk").unwrap();
+            }
+            board.run_until(Delay::from_nanos(3000)).unwrap();
+            (0..100).map(|_| {
+                board.run_for(Delay::from_nanos(100)).unwrap();
+                probe.borrow().get()
+            }).collect::<Vec<Signal>>()
+        };
+
+        // the clock keeps its edges: the drivers of a forced net aren't evaluated again
+        let forced = samples(true);
+        assert_eq!(forced, samples(false));
+        assert_eq!(forced.windows(2).filter(|w| w[0] == Signal::ZERO && w[1] == Signal::ONE).count(), 10);
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let build = || {
+            let mut board = Board::new();
+            let socket_clock = board.socket(1);
+            let socket_buffer = board.socket(2);
+            let socket_output = board.socket(1);
+            socket_clock.pin(1).unwrap().connect(&socket_buffer.pin(1).unwrap());
+            socket_buffer.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+            let mut board = board.wire();
+            let clock = Clock::new(1_000_000);
+            clock.borrow_mut().set_jitter(Delay::from_nanos(100), 7);
+            let output = ProbeOutput::new();
+            board.plug(clock).into(socket_clock).unwrap();
+            board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_buffer).unwrap();
+            board.plug(output.clone()).into(socket_output).unwrap();
+            (board.complete(), output)
+        };
+        let record = |board: &mut CompleteBoard, output: &Rc<RefCell<ProbeOutput>>| {
+            let mut values = Vec::new();
+            for _ in 0..40 {
+                board.run_for(Delay::from_nanos(95)).unwrap();
+                values.push(output.borrow().get());
+            }
+            values
+        };
+
+        // saved in the middle of a clock edge, the buffer output still pending
+        let (mut board, output) = build();
+        board.run_until(Delay::from_nanos(1505)).unwrap();
+        let path = std::env::temp_dir().join(format!("caemu-snapshot-{}", std::process::id()));
+        board.save_to_file(&path).unwrap();
+        let expected = record(&mut board, &output);
+
+        board.restore_from_file(&path).unwrap();
+        assert_eq!(board.time(), Delay::from_nanos(1505));
+        assert_eq!(record(&mut board, &output), expected);
+
+        // a fresh copy of the board picks up from there as well
+        let (mut copy, output) = build();
+        copy.restore_from_file(&path).unwrap();
+        assert_eq!(record(&mut copy, &output), expected);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut other = Board::new();
+        let socket = other.socket(1);
+        let mut other = other.wire();
+        other.plug(ProbeOutput::new()).into(socket).unwrap();
+        let mut other = other.complete();
+        let mut saved = Vec::new();
+        board.save(&mut saved);
+        assert!(matches!(other.restore(&mut &saved[..]), Err(Error::Snapshot(_))));
+
+        // truncated, or driven by a component the board doesn't have
+        assert!(matches!(copy.restore(&mut &saved[..saved.len() / 2]), Err(Error::Snapshot(_))));
+        board.nets.borrow_mut().drive(0, Driver{component: 3, pin: 0}, Signal::ONE, Delay::no_delay());
+        let mut saved = Vec::new();
+        board.save(&mut saved);
+        assert!(matches!(board.restore(&mut &saved[..]), Err(Error::Snapshot(_))));
+    }
+
+    #[test]
+    fn rewind_test() {
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_buffer = board.socket(2);
+        let socket_output = board.socket(1);
+        socket_input.pin(1).unwrap().connect(&socket_buffer.pin(1).unwrap());
+        socket_input.pin(1).unwrap().name("in");
+        socket_buffer.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+        socket_buffer.pin(2).unwrap().name("out");
+        let mut board = board.wire();
+
+        let input = ProbeInput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_buffer).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_output).unwrap();
+        let mut board = board.complete();
+        assert_eq!(board.step_back(), Err(Error::NotRecorded{time: Delay::no_delay()}));
+
+        board.record(Delay::from_nanos(100));
+        input.borrow_mut().set(Signal::ONE);
+        board.run_until(Delay::from_nanos(300)).unwrap();
+        input.borrow_mut().set(Signal::ZERO);
+        board.run_until(Delay::from_nanos(600)).unwrap();
+        board.force_net("out", Signal::ONE).unwrap();
+        board.run_until(Delay::from_nanos(900)).unwrap();
+
+        board.rewind_to(Delay::from_nanos(650)).unwrap();
+        assert_eq!(board.time(), Delay::from_nanos(650));
+        assert_eq!(board.get_net("in"), Ok(Signal::ZERO));
+        assert_eq!(board.get_net("out"), Ok(Signal::ONE));
+        board.rewind_to(Delay::from_nanos(305)).unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ZERO));
+        assert_eq!(board.get_net("out"), Ok(Signal::ONE));
+        board.rewind_to(Delay::from_nanos(200)).unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ONE));
+
+        // what came after is forgotten, the probe included
+        board.run_until(Delay::from_nanos(900)).unwrap();
+        assert_eq!(board.get_net("out"), Ok(Signal::ONE));
+
+        // one event timestamp at a time: the input at 900 ns, then the buffer at 910 ns
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+        assert_eq!(board.get_net("out"), Ok(Signal::ZERO));
+        board.step_back().unwrap();
+        assert_eq!(board.time(), Delay::from_nanos(900));
+        assert_eq!(board.get_net("in"), Ok(Signal::ZERO));
+        assert_eq!(board.get_net("out"), Ok(Signal::ONE));
+        board.step_back().unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ONE));
+        board.eval().unwrap();
+        assert_eq!(board.time(), Delay::from_nanos(910));
+        assert_eq!(board.get_net("out"), Ok(Signal::ZERO));
+
+        // only the last checkpoints are kept
+        board.set_max_checkpoints(3);
+        for i in 1..=5 {
+            input.borrow_mut().set(if i % 2 == 0 { Signal::ZERO } else { Signal::ONE });
+            board.run_until(Delay::from_nanos(1000 + 200 * i)).unwrap();
+        }
+        assert_eq!(board.rewind_to(Delay::from_nanos(1300)), Err(Error::NotRecorded{time: Delay::from_nanos(1300)}));
+        board.rewind_to(Delay::from_nanos(1850)).unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ONE));
+        board.rewind_to(Delay::from_nanos(1650)).unwrap();
+        assert_eq!(board.get_net("in"), Ok(Signal::ZERO));
+    }
+
+    #[test]
+    fn scope_test() {
+        struct Latch {
+            output: Out
+        }
+
+        impl Component for Latch {
+            fn eval(&mut self) -> Delay {
+                self.output.set(Signal::ONE);
+                Delay::no_delay()
+            }
+        }
+
+        impl Connect for Latch {
+            fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+                self.output.connect(bus);
+            }
+
+            fn get_name(&self, id: usize) -> Result<String> {
+                Ok(String::from(["q0", "le", "q1"][id - 1]))
+            }
+
+            fn pin_groups(&self) -> Vec<(String, Vec<usize>)> {
+                vec![(String::from("q"), vec![1, 3])]
+            }
+        }
+
+        let mut board = Board::new();
+        let socket_latch = board.socket(3);
+        let socket_probe = board.socket(1);
+        socket_latch.name("latch");
+        socket_latch.pin(3).unwrap().connect(&socket_probe.pin(1).unwrap());
+        let mut board = board.wire();
+        board.plug(Rc::new(RefCell::new(Latch{output: Out::new(1)}))).into(socket_latch).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_probe).unwrap();
+        let board = board.complete();
+
+        // one scope per socket, the group as a vector from its most significant bit
+        let scope = board.scope("caemu");
+        assert_eq!(scope.vars.len(), 3);
+        assert_eq!(scope.children.len(), 2);
+        let latch = &scope.children[0];
+        assert_eq!(latch.name, "latch");
+        assert_eq!(latch.vars.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(), vec!["q", "le"]);
+        assert_eq!(latch.vars[0].nets, vec![board.net_id("latch.q1").unwrap(), board.net_id("latch.q0").unwrap()]);
+        assert_eq!(scope.children[1].vars[0].nets, latch.vars[0].nets[..1].to_vec());
+    }
+}
@@ -1,55 +1,453 @@
-use std::cell::RefCell;
+use crate::delay::Delay;
+use crate::error::Result;
+use crate::snapshot::{Snapshot, invalid, take};
+
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::ops::{Not, BitAnd, BitOr};
 
+/// Four-state value of a net: HIGH is high impedance (floating), UNKNOWN is
+/// either a contention or a value that was never initialised
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Signal {
     ZERO,
     ONE,
-    HIGH
+    HIGH,
+    UNKNOWN
+}
+
+impl Signal {
+    /// True for ZERO and ONE
+    pub fn is_known(&self) -> bool {
+        *self == Signal::ZERO || *self == Signal::ONE
+    }
+
+    /// Letter of the value in VCD: 0, 1, z or x
+    pub fn to_char(self) -> char {
+        match self {
+            Signal::ZERO => '0',
+            Signal::ONE => '1',
+            Signal::HIGH => 'z',
+            Signal::UNKNOWN => 'x'
+        }
+    }
+
+    /// Value of a VCD letter, unknown for anything but 0, 1 and z
+    pub fn from_char(c: char) -> Signal {
+        match c {
+            '0' => Signal::ZERO,
+            '1' => Signal::ONE,
+            'z' | 'Z' => Signal::HIGH,
+            _ => Signal::UNKNOWN
+        }
+    }
+
+    /// Value on two bits, as in snapshots and traces
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            Signal::ZERO => 0,
+            Signal::ONE => 1,
+            Signal::HIGH => 2,
+            Signal::UNKNOWN => 3
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<Signal> {
+        match code {
+            0 => Some(Signal::ZERO),
+            1 => Some(Signal::ONE),
+            2 => Some(Signal::HIGH),
+            3 => Some(Signal::UNKNOWN),
+            _ => None
+        }
+    }
 }
 
+/// A net that was never driven
+impl Default for Signal {
+    fn default() -> Self {
+        Signal::UNKNOWN
+    }
+}
+
+impl Not for Signal {
+    type Output = Signal;
+
+    fn not(self) -> Signal {
+        match self {
+            Signal::ZERO => Signal::ONE,
+            Signal::ONE => Signal::ZERO,
+            _ => Signal::UNKNOWN
+        }
+    }
+}
+
+impl BitAnd for Signal {
+    type Output = Signal;
+
+    fn bitand(self, other: Signal) -> Signal {
+        match (self, other) {
+            (Signal::ZERO, _) | (_, Signal::ZERO) => Signal::ZERO,
+            (Signal::ONE, Signal::ONE) => Signal::ONE,
+            _ => Signal::UNKNOWN
+        }
+    }
+}
+
+impl BitOr for Signal {
+    type Output = Signal;
+
+    fn bitor(self, other: Signal) -> Signal {
+        match (self, other) {
+            (Signal::ONE, _) | (_, Signal::ONE) => Signal::ONE,
+            (Signal::ZERO, Signal::ZERO) => Signal::ZERO,
+            _ => Signal::UNKNOWN
+        }
+    }
+}
+
+#[derive(Default)]
 pub enum IOAction {
+    #[default]
     None,
     IO(Signal)
 }
 
-pub struct Bus{
+impl Snapshot for IOAction {
+    fn save(&self, out: &mut Vec<u8>) {
+        match self {
+            IOAction::None => out.push(0),
+            IOAction::IO(signal) => {
+                out.push(1);
+                signal.save(out);
+            }
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        *self = match take(input, 1)?[0] {
+            0 => IOAction::None,
+            1 => {
+                let mut signal = Signal::default();
+                signal.restore(input)?;
+                IOAction::IO(signal)
+            },
+            _ => return Err(invalid("bad read"))
+        };
+        Ok(())
+    }
+}
+
+/// Value taken by a net when none of its drivers is active
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Pull {
+    /// Resistor to VCC, the net reads ONE
+    Up,
+    /// Resistor to GND, the net reads ZERO
+    Down,
+    /// Bus keeper, the net holds the last value driven on it
+    Keeper
+}
+
+/// Propagation delays of an output pin, low to high and high to low
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PinDelay {
+    pub rise: Delay,
+    pub fall: Delay
+}
+
+/// Identifies one output pin of one plugged component
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Driver {
+    pub component: usize,
+    pub pin: usize
+}
+
+impl Snapshot for Driver {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.component.save(out);
+        self.pin.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.component.restore(input)?;
+        self.pin.restore(input)
+    }
+}
+
+/// Resolved value of every net of a board, along with the contribution of each driver
+pub struct Nets {
+    signals: Vec<Signal>,
+    raised: Vec<bool>,
+    drivers: Vec<Vec<(Driver, Signal)>>,
+    pull_up: Vec<bool>,
+    pull_down: Vec<bool>,
+    keeper: Vec<bool>,
+    forced: Vec<Option<Signal>>,
+    contended: Vec<bool>,
+    contentions: Vec<(Delay, usize, Vec<Driver>)>,
+    changed: Vec<usize>
+}
+
+impl Nets {
+    pub fn new(len: usize) -> Self {
+        Nets {
+            signals: vec![Signal::UNKNOWN; len],
+            raised: vec![false; len],
+            drivers: vec![Vec::new(); len],
+            pull_up: vec![false; len],
+            pull_down: vec![false; len],
+            keeper: vec![false; len],
+            forced: vec![None; len],
+            contended: vec![false; len],
+            contentions: Vec::new(),
+            changed: Vec::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.signals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty()
+    }
+
+    pub fn get(&self, net: usize) -> Signal {
+        self.signals[net]
+    }
+
+    pub fn signals(&self) -> &[Signal] {
+        &self.signals
+    }
+
+    pub fn raised(&self, net: usize) -> bool {
+        self.raised[net]
+    }
+
+    pub fn clear_raised(&mut self, nets: &[usize]) {
+        for net in nets {
+            self.raised[*net] = false;
+        }
+    }
+
+    /// Nets whose value changed since the last call
+    pub fn take_changed(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Adds a pull resistor or a bus keeper to a net, which then resolves to the
+    /// pulled value while nothing drives it
+    pub fn pull(&mut self, net: usize, pull: Pull) {
+        match pull {
+            Pull::Up => self.pull_up[net] = true,
+            Pull::Down => self.pull_down[net] = true,
+            Pull::Keeper => self.keeper[net] = true
+        }
+        if self.drivers[net].iter().all(|d| d.1 == Signal::HIGH) {
+            self.signals[net] = self.undriven(net);
+        }
+    }
+
+    fn undriven(&self, net: usize) -> Signal {
+        match (self.pull_up[net], self.pull_down[net]) {
+            (true, true) => Signal::UNKNOWN,
+            (true, false) => Signal::ONE,
+            (false, true) => Signal::ZERO,
+            _ if self.keeper[net] => self.signals[net],
+            _ => Signal::HIGH
+        }
+    }
+
+    /// Whether the net is pulled up or down, so that it never floats
+    pub fn pulled(&self, net: usize) -> bool {
+        self.pull_up[net] || self.pull_down[net]
+    }
+
+    /// Value driven on a net by all its drivers but one, HIGH when none of them
+    /// is active. Pulls and keepers are left out.
+    pub fn driven_without(&self, net: usize, driver: Driver) -> Signal {
+        driven(self.drivers[net].iter().filter(|d| d.0 != driver).map(|d| d.1))
+    }
+
+    /// Value driven on a net by one driver, UNKNOWN if it never drove it
+    pub fn driven_by(&self, net: usize, driver: Driver) -> Signal {
+        self.drivers[net].iter().find(|d| d.0 == driver).map(|d| d.1).unwrap_or(Signal::UNKNOWN)
+    }
+
+    /// Drivers currently taking part in the value of a net, tri-stated ones excluded
+    pub fn drivers(&self, net: usize) -> Vec<Driver> {
+        self.drivers[net].iter().filter(|d| d.1 != Signal::HIGH).map(|d| d.0).collect()
+    }
+
+    /// Every driver that ever drove a net, tri-stated ones included
+    pub(crate) fn all_drivers(&self) -> impl Iterator<Item = &Driver> {
+        self.drivers.iter().flatten().map(|d| &d.0)
+    }
+
+    /// Contentions started since the last call: time, net and the active drivers
+    pub fn take_contentions(&mut self) -> Vec<(Delay, usize, Vec<Driver>)> {
+        std::mem::take(&mut self.contentions)
+    }
+
+    /// Overrides the value of a net whatever drives it, None gives it back to its drivers
+    pub fn force(&mut self, net: usize, signal: Option<Signal>, time: Delay) {
+        self.forced[net] = signal;
+        self.resolve(net, time);
+    }
+
+    /// Records the value driven by one driver and resolves the net again
+    pub fn drive(&mut self, net: usize, driver: Driver, signal: Signal, time: Delay) {
+        let drivers = &mut self.drivers[net];
+        match drivers.iter_mut().find(|d| d.0 == driver) {
+            Some(entry) => entry.1 = signal,
+            None => drivers.push((driver, signal))
+        }
+        self.resolve(net, time);
+    }
+
+    fn resolve(&mut self, net: usize, time: Delay) {
+        // Tri-stated drivers don't take part in the resolution, the net is unknown
+        // as soon as the remaining drivers disagree or one of them drives unknown.
+        let drivers = &self.drivers[net];
+        let conflict = drivers.iter().any(|d| d.1 == Signal::ZERO) && drivers.iter().any(|d| d.1 == Signal::ONE);
+        let resolved = match (self.forced[net], driven(drivers.iter().map(|d| d.1))) {
+            (Some(forced), _) => forced,
+            (None, Signal::HIGH) => self.undriven(net),
+            (None, signal) => signal
+        };
+        if conflict && !self.contended[net] {
+            let active = drivers.iter().filter(|d| d.1 != Signal::HIGH).map(|d| d.0).collect();
+            self.contentions.push((time, net, active));
+        }
+        self.contended[net] = conflict;
+
+        if self.signals[net] == Signal::ZERO && resolved == Signal::ONE {
+            self.raised[net] = true;
+        }
+        if self.signals[net] != resolved {
+            self.changed.push(net);
+        }
+        self.signals[net] = resolved;
+    }
+}
+
+/// Everything but the pulls, which come with the board
+impl Snapshot for Nets {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.signals.save(out);
+        self.raised.save(out);
+        self.drivers.save(out);
+        self.forced.save(out);
+        self.contended.save(out);
+        self.changed.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let len = self.len();
+        self.signals.restore(input)?;
+        self.raised.restore(input)?;
+        self.drivers.restore(input)?;
+        self.forced.restore(input)?;
+        self.contended.restore(input)?;
+        self.changed.restore(input)?;
+        let lens = [self.signals.len(), self.raised.len(), self.drivers.len(), self.forced.len(), self.contended.len()];
+        if lens.iter().any(|l| *l != len) {
+            return Err(invalid("not the same number of nets"));
+        }
+        if self.changed.iter().any(|net| *net >= len) {
+            return Err(invalid("no such net"));
+        }
+        Ok(())
+    }
+}
+
+// Value forced by a set of drivers, HIGH when they are all tri-stated
+fn driven(signals: impl Iterator<Item = Signal>) -> Signal {
+    let (mut zero, mut one, mut unknown) = (false, false, false);
+    for signal in signals {
+        match signal {
+            Signal::ZERO => zero = true,
+            Signal::ONE => one = true,
+            Signal::UNKNOWN => unknown = true,
+            Signal::HIGH => ()
+        }
+    }
+    if unknown || (zero && one) {
+        Signal::UNKNOWN
+    } else if zero {
+        Signal::ZERO
+    } else if one {
+        Signal::ONE
+    } else {
+        Signal::HIGH
+    }
+}
+
+pub struct Bus {
     pub ids: Vec<usize>,
     pub read: RefCell<Vec<IOAction>>,
     pub output: Vec<IOAction>,
-    pub all_signals: Rc<RefCell<Vec<Signal>>>,
-    pub raised: Rc<RefCell<Vec<bool>>>
+    pub output_delays: Vec<Option<Delay>>,
+    pub delays: Vec<Option<PinDelay>>,
+    pub component: usize,
+    pub nets: Rc<RefCell<Nets>>,
+    pub woken: Rc<RefCell<Vec<usize>>>,
+    pub now: Rc<Cell<Delay>>
 }
 
 impl Bus {
 
     pub fn get(&self, index: usize) -> Signal {
-        let res = self.all_signals.borrow()[self.ids[index]];
+        let res = self.nets.borrow().get(self.ids[index]);
         self.read.borrow_mut()[index] = IOAction::IO(res);
         res
     }
 
+    /// Value driven on a pin's net by everything but this pin
+    pub fn driven_by_others(&self, index: usize) -> Signal {
+        let nets = self.nets.borrow();
+        self.read.borrow_mut()[index] = IOAction::IO(nets.get(self.ids[index]));
+        nets.driven_without(self.ids[index], Driver{component: self.component, pin: index})
+    }
+
+    /// Current time of the board
+    pub fn now(&self) -> Delay {
+        self.now.get()
+    }
+
     pub fn raised(&self, index: usize) -> bool {
-        let res = self.all_signals.borrow()[self.ids[index]];
-        self.read.borrow_mut()[index] = IOAction::IO(res);
-        return self.raised.borrow()[self.ids[index]];
+        let nets = self.nets.borrow();
+        self.read.borrow_mut()[index] = IOAction::IO(nets.get(self.ids[index]));
+        nets.raised(self.ids[index])
     }
 
     pub fn set(&mut self, index: usize, signal: Signal) {
         self.output[index] = IOAction::IO(signal);
     }
 
+    /// Sets an output with its own delay, instead of the one returned by eval
+    pub fn set_after(&mut self, index: usize, signal: Signal, delay: Delay) {
+        self.output[index] = IOAction::IO(signal);
+        self.output_delays[index] = Some(delay);
+    }
+
+    /// Asks the board to evaluate this component on its next run, for changes that
+    /// don't come from its inputs (a probe set from a test, for instance)
+    pub fn wake(&self) {
+        self.woken.borrow_mut().push(self.component);
+    }
+
     pub fn is_dirty(&self) -> bool {
-        let all_signals = self.all_signals.borrow();
+        let nets = self.nets.borrow();
         for (pos, read) in self.read.borrow().iter().enumerate() {
             if let IOAction::IO(signal) = read {
-                let signal_pos = self.ids[pos];
-                if *signal != all_signals[signal_pos] {
+                if *signal != nets.get(self.ids[pos]) {
                     return true;
                 }
             }
         }
-        return false;
+        false
     }
 
     pub fn clear(&mut self) {
@@ -59,18 +457,145 @@ impl Bus {
         for i in self.output.iter_mut() {
             *i = IOAction::None
         }
+        for i in self.output_delays.iter_mut() {
+            *i = None
+        }
     }
 
-    pub fn apply(&mut self) {
-        let mut all_signals = self.all_signals.borrow_mut();
-        let mut raised = self.raised.borrow_mut();
-        for (pos, id) in self.ids.iter().enumerate() {
-            if let IOAction::IO(signal) = self.output[pos] {
-                if all_signals[*id] == Signal::ZERO && signal == Signal::ONE {
-                    raised[*id] = true;
-                }
-                all_signals[*id] = signal;
+    /// Value this component drives on a pin's net
+    pub fn driving(&self, index: usize) -> Signal {
+        self.nets.borrow().driven_by(self.ids[index], Driver{component: self.component, pin: index})
+    }
+
+    /// Outputs set by the last eval, with the delay after which each one applies:
+    /// its own if set with one, the rise or fall delay of the pin when it goes from
+    /// its `previous` level to ONE or ZERO, the one returned by eval otherwise (when
+    /// leaving high impedance or an unknown value, for instance)
+    pub fn outputs(&self, eval_delay: Delay, previous: impl Fn(usize) -> Signal) -> Vec<(usize, Signal, Delay)> {
+        let mut res = Vec::new();
+        for (pos, output) in self.output.iter().enumerate() {
+            if let IOAction::IO(signal) = output {
+                let delay = match (self.output_delays[pos], self.delays[pos], previous(pos).is_known(), signal) {
+                    (Some(delay), _, _, _) => delay,
+                    (None, Some(pin), true, Signal::ONE) => pin.rise,
+                    (None, Some(pin), true, Signal::ZERO) => pin.fall,
+                    _ => eval_delay
+                };
+                res.push((pos, *signal, delay));
             }
         }
+        res
+    }
+
+    /// Applies the value of one output to its net
+    pub fn drive(&self, index: usize, signal: Signal, time: Delay) {
+        let driver = Driver{component: self.component, pin: index};
+        self.nets.borrow_mut().drive(self.ids[index], driver, signal, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::*;
+    use crate::error::Error;
+
+    #[test]
+    fn contention_test() {
+        let rom = Driver{component: 0, pin: 3};
+        let ram = Driver{component: 1, pin: 3};
+        let mut nets = Nets::new(1);
+
+        // one driver, the other one tri-stated: no contention
+        nets.drive(0, rom, Signal::ONE, Delay::from_nanos(10));
+        nets.drive(0, ram, Signal::HIGH, Delay::from_nanos(10));
+        assert_eq!(nets.get(0), Signal::ONE);
+        assert!(nets.take_contentions().is_empty());
+
+        // both drivers agree: no contention
+        nets.drive(0, ram, Signal::ONE, Delay::from_nanos(20));
+        assert!(nets.take_contentions().is_empty());
+
+        // drivers disagree: reported once, while it lasts
+        nets.drive(0, ram, Signal::ZERO, Delay::from_nanos(30));
+        nets.drive(0, rom, Signal::ONE, Delay::from_nanos(40));
+        assert_eq!(nets.get(0), Signal::UNKNOWN);
+        assert_eq!(nets.take_contentions(), vec![(Delay::from_nanos(30), 0, vec![rom, ram])]);
+
+        // releasing the bus ends the contention, the remaining driver wins
+        nets.drive(0, rom, Signal::HIGH, Delay::from_nanos(50));
+        assert_eq!(nets.get(0), Signal::ZERO);
+        nets.drive(0, rom, Signal::ONE, Delay::from_nanos(60));
+        assert_eq!(nets.take_contentions().len(), 1);
+    }
+
+    #[test]
+    fn four_state_test() {
+        assert_eq!(!Signal::ZERO, Signal::ONE);
+        assert_eq!(!Signal::HIGH, Signal::UNKNOWN);
+        assert_eq!(Signal::ZERO & Signal::UNKNOWN, Signal::ZERO);
+        assert_eq!(Signal::ONE & Signal::HIGH, Signal::UNKNOWN);
+        assert_eq!(Signal::ONE | Signal::UNKNOWN, Signal::ONE);
+        assert_eq!(Signal::ZERO | Signal::HIGH, Signal::UNKNOWN);
+
+        // nothing drove the net yet
+        let mut nets = Nets::new(1);
+        assert_eq!(nets.get(0), Signal::UNKNOWN);
+
+        // only tri-stated drivers: the net floats
+        nets.drive(0, Driver{component: 0, pin: 0}, Signal::HIGH, Delay::no_delay());
+        assert_eq!(nets.get(0), Signal::HIGH);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn snapshot_test() {
+        let mut nets = Nets::new(2);
+        nets.drive(1, Driver{component: 0, pin: 0}, Signal::ONE, Delay::no_delay());
+        let mut saved = Vec::new();
+        nets.save(&mut saved);
+        let mut copy = Nets::new(2);
+        copy.restore(&mut &saved[..]).unwrap();
+        assert_eq!(copy.signals(), nets.signals());
+        assert_eq!(copy.take_changed(), vec![1]);
+
+        // truncated, or edited with a net too many
+        assert!(matches!(Nets::new(2).restore(&mut &saved[..saved.len() - 1]), Err(Error::Snapshot(_))));
+        let mut edited = Nets::new(2);
+        edited.raised.push(false);
+        let mut saved = Vec::new();
+        edited.save(&mut saved);
+        assert!(matches!(Nets::new(2).restore(&mut &saved[..]), Err(Error::Snapshot(_))));
+        let mut edited = Nets::new(2);
+        edited.changed.push(2);
+        let mut saved = Vec::new();
+        edited.save(&mut saved);
+        assert!(matches!(Nets::new(2).restore(&mut &saved[..]), Err(Error::Snapshot(_))));
+    }
+
+    #[test]
+    fn pull_test() {
+        let first = Driver{component: 0, pin: 0};
+        let second = Driver{component: 1, pin: 0};
+        let mut nets = Nets::new(3);
+        nets.pull(0, Pull::Up);
+        nets.pull(1, Pull::Down);
+        nets.pull(2, Pull::Keeper);
+        assert_eq!(nets.signals(), &[Signal::ONE, Signal::ZERO, Signal::UNKNOWN]);
+
+        // open collector outputs on a pulled up net: wired AND
+        nets.drive(0, first, Signal::HIGH, Delay::no_delay());
+        nets.drive(0, second, Signal::ZERO, Delay::no_delay());
+        assert_eq!(nets.get(0), Signal::ZERO);
+        nets.drive(0, second, Signal::HIGH, Delay::no_delay());
+        assert_eq!(nets.get(0), Signal::ONE);
+        assert!(nets.take_contentions().is_empty());
+
+        // the pull never fights an active driver
+        nets.drive(1, first, Signal::ONE, Delay::no_delay());
+        assert_eq!(nets.get(1), Signal::ONE);
+
+        // the keeper holds the last driven value
+        nets.drive(2, first, Signal::ONE, Delay::no_delay());
+        nets.drive(2, first, Signal::HIGH, Delay::no_delay());
+        assert_eq!(nets.get(2), Signal::ONE);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::bus::{Bus, Signal};
+use crate::component::{Component, Connect, Out, PinKind};
+use crate::delay::Delay;
+use crate::error::Result;
+use crate::snapshot::Snapshot;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// Free running clock source on a single pin, waking itself up on every edge
+pub struct Clock {
+    out: Out,
+    next: Delay,
+    period: u64,
+    high: u64,
+    phase: u64,
+    jitter: u64,
+    seed: u64,
+    offset: u64,
+    level: Signal
+}
+
+/// Highest frequency, for both halves of the period to last at least 1 ps
+pub const MAX_FREQUENCY: u64 = 500_000_000_000;
+
+impl Clock {
+    /// Clock at the given frequency in Hz, 50% duty cycle, first rising edge at time 0.
+    /// Panics if the frequency is 0 or above `MAX_FREQUENCY`.
+    pub fn new(frequency: u64) -> Rc<RefCell<Self>> {
+        assert!(frequency > 0 && frequency <= MAX_FREQUENCY,
+            "clock frequency of {} Hz, not between 1 Hz and {} Hz", frequency, MAX_FREQUENCY);
+        let period = Delay::from_seconds(1).picoseconds / frequency;
+        Rc::from(RefCell::from(Clock{
+            out: Out::new(1),
+            next: Delay::no_delay(),
+            period,
+            high: period / 2,
+            phase: 0,
+            jitter: 0,
+            seed: 0,
+            offset: 0,
+            level: Signal::UNKNOWN
+        }))
+    }
+
+    /// Part of the period spent high, in percent. Each half lasts at least 1 ps.
+    /// Panics if the percentage is 0 or 100 and above, as the clock would never toggle.
+    pub fn set_duty_cycle(&mut self, percent: u64) {
+        assert!(percent > 0 && percent < 100, "clock duty cycle of {}%, not between 1% and 99%", percent);
+        self.high = (self.period * percent / 100).max(1).min(self.period - 1);
+    }
+
+    /// Time of the first rising edge
+    pub fn set_phase(&mut self, phase: Delay) {
+        self.phase = phase.picoseconds;
+    }
+
+    /// Moves every edge randomly by up to `max` in either direction around its
+    /// nominal time; the same seed always gives the same edges
+    pub fn set_jitter(&mut self, max: Delay, seed: u64) {
+        self.jitter = max.picoseconds;
+        self.seed = match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => 1,
+            seed => seed
+        };
+        self.offset = self.jitter;
+    }
+
+    /// Delay until the next edge, `nominal` after the previous one without jitter
+    fn next_edge(&mut self, nominal: u64) -> Delay {
+        if self.jitter == 0 {
+            return Delay::from_picos(nominal);
+        }
+        // xorshift, good enough to spread edges and reproducible. Offsets are kept
+        // positive (centered on `jitter`) so that they don't accumulate.
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        let offset = self.seed % (2 * self.jitter + 1);
+        let next = (nominal + offset).saturating_sub(self.offset).max(1);
+        self.offset = offset;
+        Delay::from_picos(next)
+    }
+}
+
+impl Connect for Clock {
+    fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+        self.out.connect(bus);
+    }
+
+    fn get_name(&self, _id: usize) -> Result<String> {
+        Ok(String::from("clk"))
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::Output)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+}
+
+/// Where the clock is in its cycle, the settings stay as they are
+impl Snapshot for Clock {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.next.save(out);
+        self.seed.save(out);
+        self.offset.save(out);
+        self.level.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.next.restore(input)?;
+        self.seed.restore(input)?;
+        self.offset.restore(input)?;
+        self.level.restore(input)
+    }
+}
+
+impl Component for Clock {
+    fn eval(&mut self) -> Delay {
+        let next = match self.level {
+            Signal::ONE => {
+                self.level = Signal::ZERO;
+                self.period - self.high
+            },
+            Signal::ZERO => {
+                self.level = Signal::ONE;
+                self.high
+            },
+            _ if self.phase == 0 => {
+                self.level = Signal::ONE;
+                self.high
+            },
+            _ => {
+                self.level = Signal::ZERO;
+                self.phase
+            }
+        };
+        self.next = self.next_edge(next);
+        self.out.set(self.level);
+        Delay::no_delay()
+    }
+
+    fn wakeups(&mut self, now: Delay) -> Vec<Delay> {
+        vec![now.plus(&self.next)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, CompleteBoard};
+    use crate::bus::Signal;
+    use crate::clock::Clock;
+    use crate::delay::Delay;
+    use crate::tester::ProbeOutput;
+
+    #[test]
+    fn clock_test() {
+        let mut board = Board::new();
+        let socket_clock = board.socket(1);
+        let socket_probe = board.socket(1);
+        socket_clock.pin(1).unwrap().connect(&socket_probe.pin(1).unwrap());
+        let mut board = board.wire();
+
+        // 1 MHz, high for a quarter of the period, first rising edge at 100 ns
+        let clock = Clock::new(1_000_000);
+        clock.borrow_mut().set_duty_cycle(25);
+        clock.borrow_mut().set_phase(Delay::from_nanos(100));
+        let probe = ProbeOutput::new();
+        board.plug(clock).into(socket_clock).unwrap();
+        board.plug(probe.clone()).into(socket_probe).unwrap();
+        let mut board = board.complete();
+
+        let expect = |board: &mut CompleteBoard, time: u64, signal: Signal| {
+            board.run_until(Delay::from_nanos(time)).unwrap();
+            assert_eq!(probe.borrow().get(), signal, "at {} ns", time);
+        };
+        expect(&mut board, 50, Signal::ZERO);
+        expect(&mut board, 150, Signal::ONE);
+        expect(&mut board, 349, Signal::ONE);
+        expect(&mut board, 351, Signal::ZERO);
+        expect(&mut board, 1099, Signal::ZERO);
+        expect(&mut board, 1101, Signal::ONE);
+    }
+
+    #[test]
+    #[should_panic(expected = "clock frequency of 0 Hz")]
+    fn no_frequency_test() {
+        Clock::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "clock frequency of 2000000000000 Hz")]
+    fn frequency_too_high_test() {
+        Clock::new(2_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "clock duty cycle of 150%")]
+    fn duty_cycle_test() {
+        Clock::new(1_000_000).borrow_mut().set_duty_cycle(150);
+    }
+
+    #[test]
+    fn jitter_test() {
+        let edges = |seed: u64| {
+            let clock = Clock::new(1_000_000);
+            clock.borrow_mut().set_jitter(Delay::from_nanos(10), seed);
+            let mut time = 0;
+            let mut edges = Vec::new();
+            for _ in 0..100 {
+                time += clock.borrow_mut().next_edge(500_000).picoseconds;
+                edges.push(time);
+            }
+            edges
+        };
+        let first = edges(42);
+        assert_eq!(first, edges(42));
+        assert_ne!(first, edges(43));
+        for (i, edge) in first.iter().enumerate() {
+            let nominal = (i as u64 + 1) * 500_000;
+            assert!(*edge + 10_000 >= nominal && *edge <= nominal + 10_000);
+        }
+    }
+}
@@ -1,16 +1,88 @@
 use crate::delay::Delay;
-use crate::bus::{Bus, Signal};
+use crate::bus::{Bus, Signal, PinDelay};
+use crate::error::{Error, Result};
+use crate::board::CompleteBoard;
+use crate::snapshot::Snapshot;
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
 pub trait Component {
+    /// Called once when the board completes, then each time a net read by the last
+    /// eval changes, or when woken up. A component changed from outside the board
+    /// has to call `Bus::wake` to be evaluated again.
     fn eval(&mut self) -> Delay;
+
+    /// Called right after each eval with the current time: returns the times at which
+    /// the component wants to be evaluated again, even if none of its inputs change
+    /// (oscillators, timers, refresh cycles...). Times before now are ignored.
+    fn wakeups(&mut self, _now: Delay) -> Vec<Delay> {
+        Vec::new()
+    }
+
+    /// The board behind a component made of other components, whose nets get logged
+    /// in a scope of their own
+    fn board(&self) -> Option<&CompleteBoard> {
+        None
+    }
+
+    /// Error that stopped the component, returned by the run of the board evaluating it
+    fn error(&self) -> Option<&Error> {
+        None
+    }
+}
+
+/// What a pin does, for the design rule check
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PinKind {
+    Input,
+    /// Always driven
+    Output,
+    /// Driven or released (high impedance)
+    TriState,
+    /// Read, and driven or released
+    InOut,
+    /// Supply input of a chip: vcc, vdd
+    Power,
+    /// Ground input of a chip: gnd, vss
+    Ground,
+    /// Output of a supply rail
+    Supply,
+    /// Not connected inside the chip
+    NoConnect
 }
 
 pub trait Connect {
     fn connect(&mut self, bus: Rc<RefCell<Bus>>);
-    fn get_name(&self, id: usize) -> String;
+    fn get_name(&self, id: usize) -> Result<String>;
+
+    /// Number of pins, checked against the socket size when plugged. None when the
+    /// component fits any socket.
+    fn pin_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Rise and fall delays of an output pin, when they don't depend on the evaluation
+    fn get_delay(&self, _id: usize) -> Option<PinDelay> {
+        None
+    }
+
+    /// Kind of a pin, None when unknown: such pins are left out of the design rule check
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        None
+    }
+
+    /// Pins read or driven together, by name, listed from the least significant bit.
+    /// Each group is logged as one vector, from its last pin.
+    fn pin_groups(&self) -> Vec<(String, Vec<usize>)> {
+        Vec::new()
+    }
+
+    /// Internal state saved and restored with the board, None when the component
+    /// has none: registers, memory content, latched values...
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        None
+    }
 }
 
 
@@ -75,7 +147,7 @@ impl InBus {
     }
 
     pub fn get(&self, id: usize) -> Signal {
-        return self.inputs[id].get();
+        self.inputs[id].get()
     }
 
     pub fn get_u8(&self) -> u8 {
@@ -83,7 +155,7 @@ impl InBus {
         for i in 0..8 {
             if i < self.inputs.len() {
                 if self.inputs[i].get() == Signal::ONE {
-                    res = res | (1 << i);
+                    res |= 1 << i;
                 }
             } else {
                 break;
@@ -97,7 +169,7 @@ impl InBus {
         for i in 0..16 {
             if i < self.inputs.len() {
                 if self.inputs[i].get() == Signal::ONE {
-                    res = res | (1 << i);
+                    res |= 1 << i;
                 }
             } else {
                 break;
@@ -106,6 +178,32 @@ impl InBus {
         res
     }
 
+    /// Same as get_u8, but None if one of the bits is floating or unknown
+    pub fn try_get_u8(&self) -> Option<u8> {
+        let mut res : u8 = 0;
+        for (i, input) in self.inputs.iter().take(8).enumerate() {
+            match input.get() {
+                Signal::ONE => res |= 1 << i,
+                Signal::ZERO => (),
+                _ => return None
+            }
+        }
+        Some(res)
+    }
+
+    /// Same as get_u16, but None if one of the bits is floating or unknown
+    pub fn try_get_u16(&self) -> Option<u16> {
+        let mut res : u16 = 0;
+        for (i, input) in self.inputs.iter().take(16).enumerate() {
+            match input.get() {
+                Signal::ONE => res |= 1 << i,
+                Signal::ZERO => (),
+                _ => return None
+            }
+        }
+        Some(res)
+    }
+
     pub fn connect(&mut self, bus:Rc<RefCell<Bus>>) {
         for input in self.inputs.iter_mut() {
             input.connect(bus.clone());
@@ -123,6 +221,12 @@ impl Out {
         }
     }
 
+    pub fn set_after(&mut self, signal: Signal, delay: Delay) {
+        if let Some(bus) = &self.bus {
+            bus.borrow_mut().set_after(self.id, signal, delay);
+        }
+    }
+
     pub fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
         self.bus = Some(bus)
     }
@@ -156,6 +260,12 @@ impl InOut {
             bus.borrow_mut().set(self.id, signal);
         }
     }
+
+    pub fn set_after(&mut self, signal: Signal, delay: Delay) {
+        if let Some(bus) = &self.bus {
+            bus.borrow_mut().set_after(self.id, signal, delay);
+        }
+    }
 }
 
 impl InOutBus {
@@ -168,19 +278,29 @@ impl InOutBus {
     }
 
     pub fn get(&self, id: usize) -> Signal {
-        return self.ios[id].get();
+        self.ios[id].get()
     }
 
     pub fn set(&mut self, id: usize, signal: Signal) {
         self.ios[id].set(signal);
     }
 
+    pub fn set_after(&mut self, id: usize, signal: Signal, delay: Delay) {
+        self.ios[id].set_after(signal, delay);
+    }
+
     pub fn set_high(&mut self) {
         for io in self.ios.iter_mut() {
             io.set(Signal::HIGH);
         }
     }
 
+    pub fn set_unknown(&mut self) {
+        for io in self.ios.iter_mut() {
+            io.set(Signal::UNKNOWN);
+        }
+    }
+
     pub fn set_u8(&mut self, data: u8) {
         for i in 0..8 {
             if i < self.ios.len() {
@@ -200,7 +320,7 @@ impl InOutBus {
         for i in 0..8 {
             if i < self.ios.len() {
                 if self.ios[i].get() == Signal::ONE {
-                    res = res | (1 << i);
+                    res |= 1 << i;
                 }
             } else {
                 break;
@@ -209,6 +329,19 @@ impl InOutBus {
         res
     }
 
+    /// Same as get_u8, but None if one of the bits is floating or unknown
+    pub fn try_get_u8(&self) -> Option<u8> {
+        let mut res : u8 = 0;
+        for (i, io) in self.ios.iter().take(8).enumerate() {
+            match io.get() {
+                Signal::ONE => res |= 1 << i,
+                Signal::ZERO => (),
+                _ => return None
+            }
+        }
+        Some(res)
+    }
+
     pub fn connect(&mut self, bus:Rc<RefCell<Bus>>) {
         for io in self.ios.iter_mut() {
             io.connect(bus.clone());
@@ -229,12 +362,22 @@ impl OutBus {
         self.outputs[id].set(signal);
     }
 
+    pub fn set_after(&mut self, id: usize, signal: Signal, delay: Delay) {
+        self.outputs[id].set_after(signal, delay);
+    }
+
     pub fn set_high(&mut self) {
         for io in self.outputs.iter_mut() {
             io.set(Signal::HIGH);
         }
     }
 
+    pub fn set_unknown(&mut self) {
+        for io in self.outputs.iter_mut() {
+            io.set(Signal::UNKNOWN);
+        }
+    }
+
     pub fn set_u8(&mut self, data: u8) {
         for i in 0..8 {
             if i < self.outputs.len() {
@@ -0,0 +1,484 @@
+use crate::bus::Signal;
+use crate::delay::Delay;
+use crate::error::{Error, Result};
+use crate::render::hexadecimal;
+use crate::vcd::{parse_error, parse_timescale, Waveform};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Something that went on over the nets, such as a byte on a serial line
+#[derive(Debug, PartialEq, Clone)]
+pub struct Transaction {
+    pub start: Delay,
+    pub end: Delay,
+    pub text: String
+}
+
+/// Turns the values of some variables of a waveform into transactions
+pub trait Decoder {
+    /// Transactions in time order. Fails when a variable isn't in the waveform.
+    fn decode(&self, waveform: &Waveform) -> Result<Vec<Transaction>>;
+}
+
+/// Asynchronous serial line, idle high, data from the least significant bit
+pub struct Uart {
+    line: String,
+    baud: u64,
+    bits: usize
+}
+
+/// SPI frames while the chip select is low, bytes from the most significant bit
+pub struct Spi {
+    clock: String,
+    mosi: String,
+    miso: String,
+    select: String,
+    mode: u8
+}
+
+/// I2C starts, addresses, data bytes and stops, a floating line reading high
+pub struct I2c {
+    clock: String,
+    data: String
+}
+
+/// Memory cycles of a parallel bus, ending on the falling edge of the clock as with
+/// the φ2 of a 6502
+pub struct BusCycles {
+    clock: String,
+    address: String,
+    data: String,
+    rw: String
+}
+
+/// Transactions of several decoders, each on a track of its own
+#[derive(Default)]
+pub struct Annotations {
+    tracks: Vec<(String, Vec<Transaction>)>
+}
+
+/// Values of single nets before and after one of them changes
+struct Step {
+    time: Delay,
+    before: Vec<Signal>,
+    after: Vec<Signal>
+}
+
+fn missing(name: &str) -> Error {
+    Error::UnknownNet{name: String::from(name)}
+}
+
+/// Every time one of the variables changes, with the values of all of them
+fn steps(waveform: &Waveform, names: &[&str]) -> Result<Vec<Step>> {
+    let mut times = BTreeSet::new();
+    for name in names.iter() {
+        times.extend(waveform.changes(name).ok_or_else(|| missing(name))?.into_iter().map(|(time, _)| time));
+    }
+    let mut previous = vec![Signal::UNKNOWN; names.len()];
+    let mut steps = Vec::new();
+    for time in times {
+        let after : Vec<Signal> = names.iter().map(|name| waveform.value(name, time).unwrap()[0]).collect();
+        steps.push(Step{time, before: std::mem::replace(&mut previous, after.clone()), after});
+    }
+    Ok(steps)
+}
+
+/// Fails when one of the variables isn't in the waveform
+fn check(waveform: &Waveform, names: &[&str]) -> Result<()> {
+    match names.iter().find(|name| waveform.value(name, Delay::no_delay()).is_none()) {
+        Some(name) => Err(missing(name)),
+        None => Ok(())
+    }
+}
+
+/// Value of a variable just before a time, what an edge at that time latches
+fn before(waveform: &Waveform, name: &str, time: Delay) -> Result<Vec<Signal>> {
+    waveform.value(name, Delay::from_picos(time.picoseconds.saturating_sub(1))).ok_or_else(|| missing(name))
+}
+
+/// Value of bits from the most significant one, None when some are unknown
+fn number(bits: &[Signal]) -> Option<u64> {
+    bits.iter().try_fold(0, |value, bit| match bit {
+        Signal::ZERO => Some(value << 1),
+        Signal::ONE => Some((value << 1) | 1),
+        _ => None
+    })
+}
+
+impl Uart {
+    /// 8 data bits, no parity. Panics if the baud rate is 0 or above 10^12.
+    pub fn new(line: &str, baud: u64) -> Self {
+        assert!(baud > 0 && baud <= 1_000_000_000_000, "{} baud, not between 1 and 10^12", baud);
+        Uart{line: String::from(line), baud, bits: 8}
+    }
+
+    /// Panics if the data bits aren't 5 to 9
+    pub fn bits(mut self, bits: usize) -> Self {
+        assert!((5..=9).contains(&bits), "{} data bits, not between 5 and 9", bits);
+        self.bits = bits;
+        self
+    }
+}
+
+impl Decoder for Uart {
+    fn decode(&self, waveform: &Waveform) -> Result<Vec<Transaction>> {
+        let period = 1_000_000_000_000 / self.baud;
+        let bits = self.bits as u64;
+        // each bit is sampled in its middle
+        let sample = |start: u64, bit: u64| waveform.value(&self.line, Delay::from_picos(start + period * bit + period / 2)).unwrap()[0];
+        let mut transactions = Vec::new();
+        let mut ready = 0;
+        for step in steps(waveform, &[&self.line])?.iter().filter(|step| step.before[0] == Signal::ONE && step.after[0] == Signal::ZERO) {
+            let start = step.time.picoseconds;
+            // a glitch rather than a start bit if the line is high again
+            if start < ready || sample(start, 0) != Signal::ZERO {
+                continue;
+            }
+            let data : Vec<Signal> = (1..=bits).rev().map(|bit| sample(start, bit)).collect();
+            let mut text = hexadecimal(&data);
+            if let Some(c) = number(&data).filter(|c| (0x20..0x7f).contains(c)) {
+                text.push_str(&format!(" '{}'", c as u8 as char));
+            }
+            if sample(start, bits + 1) != Signal::ONE {
+                text.push_str(" framing error");
+            }
+            ready = start + period * (bits + 1) + period / 2;
+            transactions.push(Transaction{start: step.time, end: Delay::from_picos(start + period * (bits + 2)), text});
+        }
+        Ok(transactions)
+    }
+}
+
+impl Spi {
+    /// Mode 0
+    pub fn new(clock: &str, mosi: &str, miso: &str, select: &str) -> Self {
+        Spi{clock: String::from(clock), mosi: String::from(mosi), miso: String::from(miso), select: String::from(select), mode: 0}
+    }
+
+    /// Panics if the mode isn't 0 to 3
+    pub fn mode(mut self, mode: u8) -> Self {
+        assert!(mode <= 3, "SPI mode {}, not between 0 and 3", mode);
+        self.mode = mode;
+        self
+    }
+}
+
+fn bytes(bits: &[Signal]) -> String {
+    bits.chunks_exact(8).map(hexadecimal).collect::<Vec<_>>().join(" ")
+}
+
+impl Decoder for Spi {
+    fn decode(&self, waveform: &Waveform) -> Result<Vec<Transaction>> {
+        // data is sampled on rising edges when polarity and phase are the same
+        let sampled = if (self.mode >> 1) & 1 == self.mode & 1 { Signal::ONE } else { Signal::ZERO };
+        let idle = if sampled == Signal::ONE { Signal::ZERO } else { Signal::ONE };
+        check(waveform, &[&self.mosi, &self.miso])?;
+        let mut transactions = Vec::new();
+        let mut frame : Option<(Delay, Vec<Signal>, Vec<Signal>)> = None;
+        for step in steps(waveform, &[&self.clock, &self.select])? {
+            if step.after[1] == Signal::ZERO {
+                if step.before[1] != Signal::ZERO {
+                    frame = Some((step.time, Vec::new(), Vec::new()));
+                }
+            } else if let Some((start, mosi, miso)) = frame.take() {
+                if mosi.len() >= 8 {
+                    transactions.push(Transaction{start, end: step.time, text: format!("mosi {} miso {}", bytes(&mosi), bytes(&miso))});
+                }
+            }
+            if let Some((_, mosi, miso)) = &mut frame {
+                if step.before[0] == idle && step.after[0] == sampled {
+                    mosi.push(before(waveform, &self.mosi, step.time)?[0]);
+                    miso.push(before(waveform, &self.miso, step.time)?[0]);
+                }
+            }
+        }
+        Ok(transactions)
+    }
+}
+
+impl I2c {
+    pub fn new(clock: &str, data: &str) -> Self {
+        I2c{clock: String::from(clock), data: String::from(data)}
+    }
+}
+
+fn open_drain(signal: Signal) -> Signal {
+    match signal {
+        Signal::HIGH => Signal::ONE,
+        signal => signal
+    }
+}
+
+impl Decoder for I2c {
+    fn decode(&self, waveform: &Waveform) -> Result<Vec<Transaction>> {
+        let mut transactions = Vec::new();
+        let mut started = false;
+        let mut address = false;
+        let mut bits = Vec::new();
+        let mut first_bit = Delay::no_delay();
+        for step in steps(waveform, &[&self.clock, &self.data])? {
+            let (clock, data) = (open_drain(step.after[0]), open_drain(step.after[1]));
+            let (clock_before, data_before) = (open_drain(step.before[0]), open_drain(step.before[1]));
+            let at = |text: &str| Transaction{start: step.time, end: step.time, text: String::from(text)};
+            // the data line only changes while the clock is low, but for starts and stops
+            if clock_before == Signal::ONE && clock == Signal::ONE && data_before != data {
+                if data_before == Signal::ONE && data == Signal::ZERO {
+                    transactions.push(at(if started { "restart" } else { "start" }));
+                    started = true;
+                    address = true;
+                    bits.clear();
+                } else if data_before == Signal::ZERO && data == Signal::ONE {
+                    transactions.push(at("stop"));
+                    started = false;
+                }
+            } else if started && clock_before == Signal::ZERO && clock == Signal::ONE {
+                if bits.is_empty() {
+                    first_bit = step.time;
+                }
+                bits.push(data_before);
+                if bits.len() == 9 {
+                    let ack = if bits[8] == Signal::ZERO { "ack" } else { "nack" };
+                    let text = if address {
+                        let direction = match bits[7] {
+                            Signal::ONE => "read",
+                            Signal::ZERO => "write",
+                            _ => "x"
+                        };
+                        format!("address {} {} {}", hexadecimal(&bits[..7]), direction, ack)
+                    } else {
+                        format!("data {} {}", hexadecimal(&bits[..8]), ack)
+                    };
+                    transactions.push(Transaction{start: first_bit, end: step.time, text});
+                    address = false;
+                    bits.clear();
+                }
+            }
+        }
+        Ok(transactions)
+    }
+}
+
+impl BusCycles {
+    /// The address and the data being vectors, such as the pins of a processor
+    pub fn new(clock: &str, address: &str, data: &str, rw: &str) -> Self {
+        BusCycles{clock: String::from(clock), address: String::from(address), data: String::from(data), rw: String::from(rw)}
+    }
+}
+
+impl Decoder for BusCycles {
+    fn decode(&self, waveform: &Waveform) -> Result<Vec<Transaction>> {
+        check(waveform, &[&self.address, &self.data, &self.rw])?;
+        let mut transactions = Vec::new();
+        let steps = steps(waveform, &[&self.clock])?;
+        // the first cycle starts with the log
+        let mut start = steps.first().map(|step| step.time).unwrap_or_default();
+        for step in steps.iter().filter(|step| step.before[0] == Signal::ONE && step.after[0] == Signal::ZERO) {
+            let direction = match before(waveform, &self.rw, step.time)?[0] {
+                Signal::ONE => "read",
+                Signal::ZERO => "write",
+                _ => "x"
+            };
+            let text = format!("{} {} {}", direction,
+                hexadecimal(&before(waveform, &self.address, step.time)?),
+                hexadecimal(&before(waveform, &self.data, step.time)?));
+            transactions.push(Transaction{start, end: step.time, text});
+            start = step.time;
+        }
+        Ok(transactions)
+    }
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Annotations::default()
+    }
+
+    /// Decodes a waveform onto a new track
+    pub fn decode(&mut self, track: &str, decoder: &dyn Decoder, waveform: &Waveform) -> Result<()> {
+        self.tracks.push((String::from(track), decoder.decode(waveform)?));
+        Ok(())
+    }
+
+    pub fn track(&self, name: &str) -> Option<&[Transaction]> {
+        self.tracks.iter().find(|(track, _)| track == name).map(|(_, transactions)| transactions.as_slice())
+    }
+
+    /// A line per transaction, in time order: its start, its track and what it is
+    pub fn text(&self) -> String {
+        let width = self.tracks.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let mut lines : Vec<(Delay, &str, &str)> = self.tracks.iter()
+            .flat_map(|(name, transactions)| transactions.iter().map(move |t| (t.start, name.as_str(), t.text.as_str())))
+            .collect();
+        lines.sort_by_key(|(start, _, _)| *start);
+        lines.iter()
+            .map(|(start, name, text)| format!("{:>12} ps  {:width$}  {}\n", start.picoseconds, name, text, width = width))
+            .collect()
+    }
+
+    /// VCD file with the tracks added as string variables of a `decoders` scope
+    pub fn merge_vcd(&self, vcd: &str) -> Result<String> {
+        let definitions = vcd.find("$enddefinitions").ok_or_else(|| parse_error(vcd.lines().count(), "missing $enddefinitions"))?;
+        let (header, rest) = vcd.split_at(definitions);
+        let line = header.lines().count();
+        let rest = &rest["$enddefinitions".len()..];
+        let body = &rest[rest.find("$end").ok_or_else(|| parse_error(line, "missing $end"))? + "$end".len()..];
+        let scale = match header.find("$timescale") {
+            Some(index) => {
+                let timescale = &header[index + "$timescale".len()..];
+                let end = timescale.find("$end").ok_or_else(|| parse_error(line, "missing $end"))?;
+                parse_timescale(&timescale[..end].split_whitespace().collect::<String>(), line)?
+            },
+            None => 1
+        };
+
+        // changes of the tracks, in the time unit of the file
+        let mut changes : BTreeMap<u64, Vec<String>> = BTreeMap::new();
+        let mut merged = String::from(header);
+        merged.push_str("$scope module decoders $end\n");
+        for (k, (name, transactions)) in self.tracks.iter().enumerate() {
+            merged.push_str(&format!("$var string 1 D{} {} $end\n", k, name));
+            for transaction in transactions.iter() {
+                changes.entry(transaction.start.picoseconds / scale).or_default().push(format!("s{} D{}", transaction.text.replace(' ', "_"), k));
+                if transaction.end != transaction.start {
+                    changes.entry(transaction.end.picoseconds / scale).or_default().push(format!("s- D{}", k));
+                }
+            }
+        }
+        merged.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        let mut tokens = body.lines().enumerate()
+            .flat_map(|(n, content)| content.split_whitespace().map(move |token| (line + n, token)))
+            .peekable();
+        let mut changes = changes.into_iter().peekable();
+        let mut section : Vec<&str> = Vec::new();
+        while let Some((_, token)) = tokens.next_if(|(_, token)| !token.starts_with('#')) {
+            section.push(token);
+        }
+        if !section.is_empty() {
+            merged.push_str(&format!("{}\n", section.join(" ")));
+        }
+        while let Some((line, token)) = tokens.next() {
+            let time : u64 = token[1..].parse().map_err(|_| parse_error(line, "bad time"))?;
+            while let Some((at, values)) = changes.next_if(|(at, _)| *at < time) {
+                merged.push_str(&format!("#{} {}\n", at, values.join(" ")));
+            }
+            let mut section = vec![String::from(token)];
+            while let Some((_, token)) = tokens.next_if(|(_, token)| !token.starts_with('#')) {
+                section.push(String::from(token));
+            }
+            if let Some((_, values)) = changes.next_if(|(at, _)| *at == time) {
+                section.extend(values);
+            }
+            merged.push_str(&format!("{}\n", section.join(" ")));
+        }
+        for (at, values) in changes {
+            merged.push_str(&format!("#{} {}\n", at, values.join(" ")));
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::{Annotations, BusCycles, Decoder, I2c, Spi, Transaction, Uart};
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::vcd::Waveform;
+
+    fn transaction(start: u64, end: u64, text: &str) -> Transaction {
+        Transaction{start: Delay::from_nanos(start), end: Delay::from_nanos(end), text: String::from(text)}
+    }
+
+    const SERIAL : &str = "$timescale 1 ns $end\n$scope module caemu $end\n$var wire 1 ! tx $end\n$upscope $end\n\
+        $enddefinitions $end\n#0 1!\n#1000 0!\n#2000 1!\n#3000 0!\n#8000 1!\n#9000 0!\n#10000 1!\n#12000 0!\n";
+
+    #[test]
+    fn uart_test() {
+        let waveform = Waveform::parse(SERIAL).unwrap();
+        assert_eq!(Uart::new("caemu.tx", 1_000_000).decode(&waveform).unwrap(), vec![
+            transaction(1000, 11000, "41 'A'"),
+            transaction(12000, 22000, "00 framing error")
+        ]);
+        assert_eq!(Uart::new("caemu.rx", 1_000_000).decode(&waveform), Err(Error::UnknownNet{name: String::from("caemu.rx")}));
+    }
+
+    #[test]
+    fn spi_test() {
+        let mut vcd = String::from("$timescale 1 ns $end\n$scope module caemu $end\n$var wire 1 c clk $end\n\
+            $var wire 1 o mosi $end\n$var wire 1 i miso $end\n$var wire 1 s cs $end\n$upscope $end\n\
+            $enddefinitions $end\n#0 0c 1s 0o 0i\n#10 0s\n");
+        for bit in 0..8 {
+            let time = 20 + bit * 20;
+            vcd.push_str(&format!("#{} {}o {}i\n#{} 1c\n#{} 0c\n", time, (0xA5 >> (7 - bit)) & 1, (0x3C >> (7 - bit)) & 1, time + 5, time + 15));
+        }
+        vcd.push_str("#200 1s\n");
+        let waveform = Waveform::parse(&vcd).unwrap();
+        assert_eq!(Spi::new("caemu.clk", "caemu.mosi", "caemu.miso", "caemu.cs").decode(&waveform).unwrap(),
+            vec![transaction(10, 200, "mosi A5 miso 3C")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "SPI mode 4, not between 0 and 3")]
+    fn spi_mode_test() {
+        Spi::new("caemu.clk", "caemu.mosi", "caemu.miso", "caemu.cs").mode(4);
+    }
+
+    #[test]
+    #[should_panic(expected = "0 baud, not between 1 and 10^12")]
+    fn no_baud_test() {
+        Uart::new("caemu.tx", 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "0 data bits, not between 5 and 9")]
+    fn no_bits_test() {
+        Uart::new("caemu.tx", 9600).bits(0);
+    }
+
+    #[test]
+    fn i2c_test() {
+        let mut vcd = String::from("$timescale 1 ns $end\n$scope module caemu $end\n$var wire 1 c scl $end\n\
+            $var wire 1 d sda $end\n$upscope $end\n$enddefinitions $end\n#0 1c 1d\n#10 0d\n#20 0c\n");
+        // address 0x50 to write, acknowledged, then 0x12, not acknowledged
+        let bits = [1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 1];
+        for (k, bit) in bits.iter().enumerate() {
+            let time = 30 + 20 * k;
+            vcd.push_str(&format!("#{} {}d\n#{} 1c\n#{} 0c\n", time, bit, time + 5, time + 10));
+        }
+        vcd.push_str("#390 0d\n#395 1c\n#400 1d\n");
+        let waveform = Waveform::parse(&vcd).unwrap();
+        assert_eq!(I2c::new("caemu.scl", "caemu.sda").decode(&waveform).unwrap(), vec![
+            transaction(10, 10, "start"),
+            transaction(35, 195, "address 50 write ack"),
+            transaction(215, 375, "data 12 nack"),
+            transaction(400, 400, "stop")
+        ]);
+    }
+
+    #[test]
+    fn bus_cycles_test() {
+        let waveform = Waveform::parse("$timescale 1 ns $end\n$scope module cpu $end\n$var wire 1 p phi2 $end\n\
+            $var wire 16 a a [15:0] $end\n$var wire 8 d d [7:0] $end\n$var wire 1 r rw $end\n$upscope $end\n\
+            $enddefinitions $end\n#0 0p b0 a bx d 1r\n#10 1p b1000000000000 a\n#20 b10101001 d\n#30 0p\n\
+            #40 1p b1000000000001 a 0r b1 d\n#60 0p\n").unwrap();
+        assert_eq!(BusCycles::new("cpu.phi2", "cpu.a", "cpu.d", "cpu.rw").decode(&waveform).unwrap(), vec![
+            transaction(0, 30, "read 1000 A9"),
+            transaction(30, 60, "write 1001 01")
+        ]);
+    }
+
+    #[test]
+    fn annotations_test() {
+        let waveform = Waveform::parse(SERIAL).unwrap();
+        let mut annotations = Annotations::new();
+        annotations.decode("uart", &Uart::new("caemu.tx", 1_000_000), &waveform).unwrap();
+        assert_eq!(annotations.text(), "     1000000 ps  uart  41 'A'\n    12000000 ps  uart  00 framing error\n");
+
+        let merged = annotations.merge_vcd(SERIAL).unwrap();
+        assert!(merged.contains("$scope module decoders $end\n$var string 1 D0 uart $end\n$upscope $end\n$enddefinitions $end\n#0 1!\n"));
+        assert!(merged.contains("#1000 0! s41_'A' D0\n"));
+        assert!(merged.ends_with("#10000 1!\n#11000 s- D0\n#12000 0! s00_framing_error D0\n#22000 s- D0\n"));
+        let merged = Waveform::parse(&merged).unwrap();
+        assert_eq!(merged.names().collect::<Vec<_>>(), vec!["caemu.tx", "decoders.uart"]);
+    }
+}
@@ -1,5 +1,5 @@
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Copy, Clone)]
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Copy, Clone, Default)]
 pub struct Delay {
     pub picoseconds: u64
 }
@@ -0,0 +1,83 @@
+use crate::board::Oscillation;
+use crate::delay::Delay;
+
+use std::fmt;
+use std::path::Path;
+
+/// What can go wrong while building or running a board
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// A pin number outside of the socket, pins are numbered from 1
+    NoSuchPin { socket: String, pin: usize, size: usize },
+    /// Two groups of pins of different sizes connected together
+    PinCountMismatch { expected: usize, actual: usize },
+    /// A component with more or less pins than the socket it is plugged into
+    SocketSize { socket: String, expected: usize, actual: usize },
+    /// A pin number the component doesn't have
+    UnknownPin { component: String, pin: usize },
+    /// No net has this name
+    UnknownNet { name: String },
+    /// The simulation never settles
+    Oscillation(Oscillation),
+    /// A snapshot that is corrupted or was saved from another board
+    Snapshot(String),
+    /// Reading or writing a file failed
+    Io(String),
+    /// Going back to a time that wasn't recorded
+    NotRecorded { time: Delay },
+    /// A VCD file that can't be read
+    Vcd { line: usize, reason: String },
+    /// A trace file that is corrupted or wasn't closed
+    Trace(String)
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Failure to read or write a file, with its path
+    pub(crate) fn io(path: &Path, error: std::io::Error) -> Self {
+        Error::Io(format!("{}: {}", path.display(), error))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoSuchPin { socket, pin, size } =>
+                write!(f, "socket {} has no pin {}, pins go from 1 to {}", socket, pin, size),
+            Error::PinCountMismatch { expected, actual } =>
+                write!(f, "cannot connect {} pins to {} pins", expected, actual),
+            Error::SocketSize { socket, expected, actual } =>
+                write!(f, "socket {} has {} pins, the component plugged into it has {}", socket, expected, actual),
+            Error::UnknownPin { component, pin } =>
+                write!(f, "{} has no pin {}", component, pin),
+            Error::UnknownNet { name } =>
+                write!(f, "no net named {}", name),
+            Error::Oscillation(oscillation) => oscillation.fmt(f),
+            Error::Snapshot(reason) =>
+                write!(f, "invalid snapshot: {}", reason),
+            Error::Io(message) =>
+                write!(f, "{}", message),
+            Error::NotRecorded { time } =>
+                write!(f, "nothing recorded at {} ps", time.picoseconds),
+            Error::Vcd { line, reason } =>
+                write!(f, "VCD line {}: {}", line, reason),
+            Error::Trace(reason) =>
+                write!(f, "invalid trace: {}", reason)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
+
+impl From<Oscillation> for Error {
+    fn from(oscillation: Oscillation) -> Self {
+        Error::Oscillation(oscillation)
+    }
+}
@@ -4,10 +4,22 @@ pub mod component;
 pub mod delay;
 pub mod bus;
 pub mod logger;
+pub mod clock;
+pub mod error;
+pub mod supply;
+pub mod subboard;
+pub mod snapshot;
+pub mod trace;
+pub mod vcd;
+pub mod render;
+pub mod decode;
 
 #[cfg(test)]
 mod tests {
+    use crate::board::Board;
+    use crate::bus::Signal;
     use crate::delay::{Delay};
+    use crate::tester::{ProbeInput, ProbeOutput};
 
     #[test]
     fn delays_test() {
@@ -16,4 +28,25 @@ mod tests {
         assert_eq!(Delay::from_micros(1000), Delay::from_millis(1));
         assert_eq!(Delay::from_millis(1000), Delay::from_seconds(1));
     }
+
+    #[test]
+    fn pins_test() {
+        // pins are numbered from 1, as with Socket::pin
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_output = board.socket(1);
+        let socket_spare = board.socket(1);
+        socket_input.pins(&[1]).unwrap().connect(&socket_output.pins(&[1]).unwrap()).unwrap();
+        let mut board = board.wire();
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_spare).unwrap();
+        let mut board = board.complete();
+
+        input.borrow_mut().set(Signal::ONE);
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+    }
 }
\ No newline at end of file
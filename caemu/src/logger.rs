@@ -1,58 +1,532 @@
 use crate::bus::Signal;
 use crate::delay::Delay;
-
-use std::cell::Ref;
-use std::time::Instant;
+use crate::error::{Error, Result};
+use crate::trace::TraceWriter;
 
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the values of the nets go after each processed time
+pub trait Sink {
+    /// Values in the order of the scope the sink was made for
+    fn log(&mut self, signals: &[Signal], time: &Delay);
+
+    /// First write that failed, nothing gets written after it
+    fn error(&self) -> Option<&Error>;
+
+    /// Writes what is still buffered, nothing gets logged afterwards
+    fn close(&mut self) -> Result<()>;
+}
 
+/// Writes the value changes of the nets as a VCD file
 pub struct Logger {
-    previous: Vec<Signal>
+    previous: Vec<Signal>,
+    vectors: Vec<Vec<usize>>,
+    out: Box<dyn Write>,
+    error: Option<Error>,
+    closed: bool,
+    /// Last time written, and what is added to the times of the board since it was rewound
+    time: u64,
+    shift: u64
 }
 
-impl Logger {
-    pub fn new(len: usize, names: Ref<HashMap<usize, String>>) -> Self {
-        println!("$date {:?} $end", Instant::now());
-        println!("$version caemu 0.0.1 $end");
-        println!("$comment");
-        println!("   Caemu simulation logger");
-        println!("$end");
-        println!("$timescale 1 ps $end");
-        println!("$scope module caemu $end");
-        let mut previous = Vec::new();
-        for i in 0..len {
-            let name_format = match names.get(&i) {
-                None => format!("B{}", i),
-                Some(name) => format!("{}", name)
+/// Named group of variables in the log, a board or a socket
+pub struct Scope {
+    pub name: String,
+    pub vars: Vec<Var>,
+    pub children: Vec<Scope>
+}
+
+/// Nets by their index in the logged values, from the most significant bit
+pub struct Var {
+    pub name: String,
+    pub nets: Vec<usize>
+}
+
+/// Where the log of a board goes
+pub enum Log {
+    /// Nothing is logged
+    Off,
+    /// Standard output
+    Stdout,
+    /// A file, created or truncated when the board is completed
+    File(PathBuf),
+    /// Anything else
+    Writer(Box<dyn Write>),
+    /// A binary trace file instead of VCD, see `TraceWriter`
+    Trace(PathBuf),
+    /// Another log, keeping only what a capture selects
+    Captured(Box<Log>, Capture)
+}
+
+/// Variables to log, see `Capture`
+pub enum Filter {
+    /// The pins of the sockets with this name, at any depth
+    Component(String),
+    /// Variables whose full name, such as `caemu.U1.a`, matches a `*` and `?` pattern
+    Name(String),
+    /// Vectors of nets, such as the data pins of a memory, whose full name matches a pattern
+    Bus(String)
+}
+
+/// Met when the nets of a variable, by full name, have the value of an integer
+pub struct Trigger {
+    name: String,
+    value: u64,
+    delay: Delay
+}
+
+/// What a log keeps, like the setup of a logic analyzer: the variables matching
+/// any filter, between the start and stop triggers
+#[derive(Default)]
+pub struct Capture {
+    filters: Vec<Filter>,
+    start: Option<Trigger>,
+    stop: Option<Trigger>
+}
+
+/// Sink logging the selected nets of the board into another one
+struct Captured {
+    sink: Box<dyn Sink>,
+    nets: Vec<usize>,
+    start: Option<Armed>,
+    stop: Option<Armed>,
+    values: Vec<Signal>
+}
+
+/// Trigger on the nets of its variable
+struct Armed {
+    nets: Vec<usize>,
+    value: u64,
+    delay: Delay,
+    fired: Option<Delay>
+}
+
+/// Standard output through `print!`, which the test harness captures
+pub(crate) struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        print!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+impl Log {
+    /// Sink logging the nets of a scope, None when the log is off
+    pub(crate) fn open(self, scope: &Scope) -> Result<Option<Box<dyn Sink>>> {
+        let out : Box<dyn Write> = match self {
+            Log::Off => return Ok(None),
+            Log::Trace(path) => return Ok(Some(Box::new(TraceWriter::create(scope, path)?))),
+            Log::Captured(log, capture) => return capture.open(scope, *log),
+            Log::Stdout => Box::new(Stdout),
+            Log::File(path) => {
+                let file = File::create(&path).map_err(|e| Error::io(&path, e))?;
+                Box::new(BufWriter::new(file))
+            },
+            Log::Writer(out) => out
+        };
+        Ok(Some(Box::new(Logger::new(scope, out))))
+    }
+
+    /// This log, keeping only what the capture selects
+    pub fn capture(self, capture: Capture) -> Log {
+        Log::Captured(Box::new(self), capture)
+    }
+}
+
+impl Trigger {
+    pub fn new(name: &str, value: u64) -> Self {
+        Trigger{name: String::from(name), value, delay: Delay::no_delay()}
+    }
+
+    /// Fires that long after the condition is first met
+    pub fn after(mut self, delay: Delay) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn arm(&self, scope: &Scope) -> Result<Armed> {
+        let nets = find(scope, "", &self.name).ok_or_else(|| Error::UnknownNet{name: self.name.clone()})?;
+        Ok(Armed{nets, value: self.value, delay: self.delay, fired: None})
+    }
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Logs from the time the trigger fires, included
+    pub fn start(mut self, trigger: Trigger) -> Self {
+        self.start = Some(trigger);
+        self
+    }
+
+    /// Logs until the time the trigger fires, included
+    pub fn stop(mut self, trigger: Trigger) -> Self {
+        self.stop = Some(trigger);
+        self
+    }
+
+    /// Opens the log for the selected variables, renumbering their nets
+    fn open(self, scope: &Scope, log: Log) -> Result<Option<Box<dyn Sink>>> {
+        let start = self.start.as_ref().map(|trigger| trigger.arm(scope)).transpose()?;
+        let stop = self.stop.as_ref().map(|trigger| trigger.arm(scope)).transpose()?;
+        let mut nets = Vec::new();
+        let selected = self.select(scope, "", &mut nets, &mut HashMap::new());
+        Ok(log.open(&selected)?
+            .map(|sink| Box::new(Captured{sink, nets, start, stop, values: Vec::new()}) as Box<dyn Sink>))
+    }
+
+    fn select(&self, scope: &Scope, prefix: &str, nets: &mut Vec<usize>, renumbered: &mut HashMap<usize, usize>) -> Scope {
+        let path = format!("{}{}", prefix, scope.name);
+        let mut vars = Vec::new();
+        for var in scope.vars.iter().filter(|var| self.keeps(&path, var)) {
+            let var_nets = var.nets.iter().map(|net| *renumbered.entry(*net).or_insert_with(|| {
+                nets.push(*net);
+                nets.len() - 1
+            })).collect();
+            vars.push(Var{name: var.name.clone(), nets: var_nets});
+        }
+        let children = scope.children.iter()
+            .map(|child| self.select(child, &format!("{}.", path), nets, renumbered))
+            .filter(|child| !child.vars.is_empty() || !child.children.is_empty())
+            .collect();
+        Scope{name: scope.name.clone(), vars, children}
+    }
+
+    fn keeps(&self, path: &str, var: &Var) -> bool {
+        let name = format!("{}.{}", path, var.name);
+        self.filters.is_empty() || self.filters.iter().any(|filter| match filter {
+            Filter::Component(component) => path.split('.').skip(1).any(|scope| scope == component),
+            Filter::Name(pattern) => glob(pattern, &name),
+            Filter::Bus(pattern) => var.nets.len() > 1 && glob(pattern, &name)
+        })
+    }
+}
+
+/// Nets of a variable given by its full name
+fn find(scope: &Scope, prefix: &str, name: &str) -> Option<Vec<usize>> {
+    let path = format!("{}{}.", prefix, scope.name);
+    scope.vars.iter()
+        .find(|var| name.strip_prefix(path.as_str()) == Some(var.name.as_str()))
+        .map(|var| var.nets.clone())
+        .or_else(|| scope.children.iter().find_map(|child| find(child, &path, name)))
+}
+
+/// Whether a text matches a pattern where `*` stands for any text and `?` for any character
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern : Vec<char> = pattern.chars().collect();
+    let text : Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // last star seen, and where the text it covers ends, to cover more on a mismatch
+    let mut star : Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl Armed {
+    /// Time the trigger fires at, once its condition was met
+    fn check(&mut self, signals: &[Signal], time: &Delay) -> Option<Delay> {
+        if self.fired.is_none() && self.met(signals) {
+            self.fired = Some(*time);
+        }
+        self.fired.map(|fired| fired.plus(&self.delay))
+    }
+
+    fn met(&self, signals: &[Signal]) -> bool {
+        let mut value = 0u64;
+        for net in self.nets.iter() {
+            value = match signals.get(*net) {
+                Some(Signal::ZERO) => value << 1,
+                Some(Signal::ONE) => (value << 1) | 1,
+                _ => return false
             };
-            println!("$var wire 1 B{} {} $end", i, name_format);
-            previous.push(Signal::HIGH);
-        }
-        println!("$upscope $end");
-        println!("$enddefinitions $end");
-
-        Logger {previous}
-    }
-
-    pub fn log(&mut self, bus: &Vec<Signal>, current_time: &Delay) {
-        let mut has_started = false;
-        for (i, s) in bus.iter().enumerate() {
-            if *s != self.previous[i] {
-                let value = match s {
-                    Signal::ONE => "1",
-                    Signal::ZERO => "0",
-                    Signal::HIGH => "x",
-                };
-                if !has_started {
-                    print!("#{}", current_time.picoseconds);
-                    has_started = true;
+        }
+        value == self.value
+    }
+}
+
+impl Sink for Captured {
+    fn log(&mut self, signals: &[Signal], time: &Delay) {
+        // the stop trigger is only armed once logging started
+        if let Some(start) = &mut self.start {
+            if start.check(signals, time).filter(|at| at <= time).is_none() {
+                return;
+            }
+        }
+        if let Some(stop) = &mut self.stop {
+            if stop.check(signals, time).filter(|at| at < time).is_some() {
+                return;
+            }
+        }
+        self.values.clear();
+        self.values.extend(self.nets.iter().map(|net| signals[*net]));
+        self.sink.log(&self.values, time);
+    }
+
+    fn error(&self) -> Option<&Error> {
+        self.sink.error()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.sink.close()
+    }
+}
+
+impl Logger {
+    /// Declares the variables of a scope and of its children
+    pub fn new(scope: &Scope, out: Box<dyn Write>) -> Self {
+        let mut logger = Logger {previous: Vec::new(), vectors: Vec::new(), out, error: None, closed: false, time: 0, shift: 0};
+        let mut header = format!("$date {} $end\n", date(SystemTime::now()));
+        header.push_str("$version caemu 0.0.1 $end\n");
+        header.push_str("$comment\n   Caemu simulation logger\n$end\n");
+        header.push_str("$timescale 1 ps $end\n");
+        logger.declare(scope, &mut header);
+        header.push_str("$enddefinitions $end\n");
+        logger.write(&header);
+        logger
+    }
+
+    /// Single nets are known by their index, vectors get identifiers of their own
+    fn declare(&mut self, scope: &Scope, header: &mut String) {
+        header.push_str(&format!("$scope module {} $end\n", scope.name));
+        for var in scope.vars.iter() {
+            match var.nets.as_slice() {
+                [net] => header.push_str(&format!("$var wire 1 B{} {} $end\n", net, var.name)),
+                nets => {
+                    header.push_str(&format!("$var wire {} V{} {} [{}:0] $end\n",
+                        nets.len(), self.vectors.len(), var.name, nets.len() - 1));
+                    self.vectors.push(nets.to_vec());
                 }
-                print!(" {}B{}", value, i);
-                self.previous[i] = *s;
             }
         }
-        if has_started {
-            println!();
+        for child in scope.children.iter() {
+            self.declare(child, header);
+        }
+        header.push_str("$upscope $end\n");
+    }
+
+    /// Writes the values that changed. After a rewind, times go on from the last one
+    /// written, as VCD times only increase.
+    pub fn log(&mut self, bus: &[Signal], current_time: &Delay) {
+        let rewound = current_time.picoseconds + self.shift < self.time;
+        if rewound {
+            self.shift = self.time - current_time.picoseconds;
+        }
+        // everything starts unknown, a value known from the start is logged at time 0
+        self.previous.resize(bus.len(), Signal::UNKNOWN);
+        let changed : Vec<bool> = bus.iter().zip(self.previous.iter()).map(|(s, p)| rewound || s != p).collect();
+        let mut line = String::new();
+        for (i, s) in bus.iter().enumerate().filter(|(i, _)| changed[*i]) {
+            line.push_str(&format!(" {}B{}", s.to_char(), i));
+        }
+        for (i, nets) in self.vectors.iter().enumerate() {
+            if nets.iter().any(|net| changed[*net]) {
+                let bits : String = nets.iter().map(|net| bus[*net].to_char()).collect();
+                line.push_str(&format!(" b{} V{}", bits, i));
+            }
+        }
+        self.previous.copy_from_slice(bus);
+        if rewound {
+            let text = format!("$comment rewound to {} ps, later times shifted by {} ps $end\n$dumpvars{} $end\n",
+                current_time.picoseconds, self.shift, line);
+            self.write(&text);
+        } else if !line.is_empty() {
+            self.time = current_time.picoseconds + self.shift;
+            line.insert_str(0, &format!("#{}", self.time));
+            line.push('\n');
+            self.write(&line);
+        }
+    }
+
+    /// First write that failed, nothing gets written after it
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Flushes what is buffered, nothing gets written afterwards
+    pub fn close(&mut self) -> Result<()> {
+        if self.error.is_none() && !self.closed {
+            if let Err(error) = self.out.flush() {
+                self.error = Some(Error::from(error));
+            }
+        }
+        self.closed = true;
+        match &self.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(())
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.error.is_none() && !self.closed {
+            if let Err(error) = self.out.write_all(text.as_bytes()) {
+                self.error = Some(Error::from(error));
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+impl Sink for Logger {
+    fn log(&mut self, signals: &[Signal], time: &Delay) {
+        Logger::log(self, signals, time);
+    }
+
+    fn error(&self) -> Option<&Error> {
+        Logger::error(self)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        Logger::close(self)
+    }
+}
+
+/// UTC date and time, such as 2021-03-14 15:09:26 UTC
+fn date(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // civil date from days since 1970-01-01, in 400 year eras of 146097 days
+    // starting on March 1st
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day,
+        seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Signal;
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::logger::{date, glob, Capture, Filter, Log, Logger, Scope, Trigger, Var};
+    use crate::vcd::Recording;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn date_test() {
+        assert_eq!(date(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(date(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(date(UNIX_EPOCH + Duration::from_secs(1_615_734_566)), "2021-03-14 15:09:26 UTC");
+    }
+
+    #[test]
+    fn writer_test() {
+        let out = Recording::new();
+        let scope = Scope{name: String::from("caemu"), vars: vec![Var{name: String::from("clk"), nets: vec![0]}], children: Vec::new()};
+        let mut logger = Logger::new(&scope, Box::new(out.clone()));
+        logger.log(&[Signal::ONE], &Delay::from_nanos(5));
+        logger.log(&[Signal::ONE], &Delay::from_nanos(6));
+        let text = String::from_utf8(out.bytes()).unwrap();
+        assert!(text.contains("$var wire 1 B0 clk $end\n$upscope $end\n$enddefinitions $end\n"));
+        assert!(text.ends_with("$enddefinitions $end\n#5000 1B0\n"));
+        assert!(logger.error().is_none());
+
+        logger.close().unwrap();
+        logger.log(&[Signal::ZERO], &Delay::from_nanos(7));
+        assert_eq!(out.bytes().len(), text.len());
+    }
+
+    #[test]
+    fn vector_test() {
+        let out = Recording::new();
+        let nets = (0..3).map(|net| Var{name: format!("n{}", net), nets: vec![net]}).collect();
+        let chip = Scope{name: String::from("U1"), vars: vec![
+            Var{name: String::from("d"), nets: vec![2, 1]},
+            Var{name: String::from("en"), nets: vec![0]}
+        ], children: Vec::new()};
+        let scope = Scope{name: String::from("caemu"), vars: nets, children: vec![chip]};
+        let mut logger = Logger::new(&scope, Box::new(out.clone()));
+        logger.log(&[Signal::ONE, Signal::ZERO, Signal::ONE], &Delay::no_delay());
+        logger.log(&[Signal::ZERO, Signal::ZERO, Signal::ONE], &Delay::from_picos(10));
+        logger.log(&[Signal::ZERO, Signal::HIGH, Signal::ONE], &Delay::from_picos(20));
+        let text = String::from_utf8(out.bytes()).unwrap();
+        assert!(text.contains("$scope module U1 $end\n$var wire 2 V0 d [1:0] $end\n$var wire 1 B0 en $end\n$upscope $end\n"));
+        assert!(text.ends_with("#0 1B0 0B1 1B2 b10 V0\n#10 0B0\n#20 zB1 b1z V0\n"));
+    }
+
+    #[test]
+    fn rewind_test() {
+        let out = Recording::new();
+        let scope = Scope{name: String::from("caemu"), vars: vec![Var{name: String::from("clk"), nets: vec![0]}], children: Vec::new()};
+        let mut logger = Logger::new(&scope, Box::new(out.clone()));
+        logger.log(&[Signal::ONE], &Delay::from_picos(30));
+        logger.log(&[Signal::ONE], &Delay::from_picos(10));
+        logger.log(&[Signal::ZERO], &Delay::from_picos(20));
+        let text = String::from_utf8(out.bytes()).unwrap();
+        assert!(text.ends_with("#30 1B0\n$comment rewound to 10 ps, later times shifted by 20 ps $end\n$dumpvars 1B0 $end\n#40 0B0\n"));
+    }
+
+    #[test]
+    fn glob_test() {
+        assert!(glob("caemu.U*.d", "caemu.U12.d"));
+        assert!(glob("*.?", "caemu.U1.a"));
+        assert!(glob("*a*a", "banana"));
+        assert!(!glob("caemu.U?.d", "caemu.U12.d"));
+        assert!(!glob("*.d", "caemu.U1.dd"));
+    }
+
+    #[test]
+    fn capture_test() {
+        let out = Recording::new();
+        let nets = ["reset", "a", "b"].iter().enumerate().map(|(net, name)| Var{name: String::from(*name), nets: vec![net]}).collect();
+        let chip = Scope{name: String::from("U1"), vars: vec![
+            Var{name: String::from("d"), nets: vec![2, 1]},
+            Var{name: String::from("en"), nets: vec![0]}
+        ], children: Vec::new()};
+        let scope = Scope{name: String::from("caemu"), vars: nets, children: vec![chip]};
+        let capture = Capture::new()
+            .filter(Filter::Name(String::from("caemu.a")))
+            .filter(Filter::Bus(String::from("*.d")))
+            .start(Trigger::new("caemu.reset", 1))
+            .stop(Trigger::new("caemu.U1.d", 0b11).after(Delay::from_picos(10)));
+        let mut logger = Log::Writer(Box::new(out.clone())).capture(capture).open(&scope).unwrap().unwrap();
+        logger.log(&[Signal::ZERO, Signal::ONE, Signal::ZERO], &Delay::no_delay());
+        logger.log(&[Signal::ONE, Signal::ONE, Signal::ZERO], &Delay::from_picos(10));
+        logger.log(&[Signal::ONE, Signal::ZERO, Signal::ONE], &Delay::from_picos(20));
+        logger.log(&[Signal::ONE, Signal::ONE, Signal::ONE], &Delay::from_picos(30));
+        logger.log(&[Signal::ONE, Signal::ZERO, Signal::ONE], &Delay::from_picos(40));
+        logger.log(&[Signal::ONE, Signal::ONE, Signal::ONE], &Delay::from_picos(50));
+        let text = String::from_utf8(out.bytes()).unwrap();
+        assert!(text.contains("$scope module caemu $end\n$var wire 1 B0 a $end\n$scope module U1 $end\n$var wire 2 V0 d [1:0] $end\n$upscope $end\n"));
+        assert!(text.ends_with("$enddefinitions $end\n#10 1B0 0B1 b01 V0\n#20 0B0 1B1 b10 V0\n#30 1B0 b11 V0\n#40 0B0 b10 V0\n"));
+
+        let capture = Capture::new().start(Trigger::new("caemu.U2.d", 1));
+        assert_eq!(Log::Off.capture(capture).open(&scope).err(), Some(Error::UnknownNet{name: String::from("caemu.U2.d")}));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::bus::Signal;
+use crate::delay::Delay;
+use crate::vcd::Waveform;
+
+/// Draws the end of a waveform as text, with vectors in hexadecimal
+///
+/// ```text
+/// time      0 ps    15000 ps
+/// caemu.clk ___---___---___-
+/// caemu.d   012   |013  |x
+/// ```
+pub struct Render {
+    window: Delay,
+    columns: usize,
+    unicode: bool
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Render::new()
+    }
+}
+
+impl Render {
+    pub fn new() -> Self {
+        Render{window: Delay::from_micros(1), columns: 64, unicode: false}
+    }
+
+    /// Duration drawn, up to the end time, 1 µs by default
+    pub fn window(mut self, window: Delay) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Characters for the whole window, 64 by default
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Draws with Unicode blocks and lines rather than ASCII
+    pub fn unicode(mut self) -> Self {
+        self.unicode = true;
+        self
+    }
+
+    /// Draws the variables with these full names over the window ending at `end`
+    pub fn draw(&self, waveform: &Waveform, names: &[&str], end: Delay) -> String {
+        let start = end.picoseconds.saturating_sub(self.window.picoseconds);
+        let span = end.picoseconds - start;
+        let columns = (self.columns as u64).min(span).max(1);
+        let times : Vec<Delay> = (1..=columns).map(|c| Delay::from_picos(start + span * c / columns)).collect();
+
+        let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0).max(4);
+        let mut text = format!("{:width$} {}\n", "time", ruler(start, end.picoseconds, columns as usize), width = width);
+        for name in names.iter() {
+            let samples : Option<Vec<Vec<Signal>>> = times.iter().map(|time| waveform.value(name, *time)).collect();
+            let line = match samples {
+                None => String::from("not in the log"),
+                Some(samples) if samples.iter().all(|value| value.len() == 1) =>
+                    samples.iter().map(|value| self.level(value[0])).collect(),
+                Some(samples) => self.vector(&samples)
+            };
+            text.push_str(format!("{:width$} {}", name, line, width = width).trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn level(&self, signal: Signal) -> char {
+        let levels = if self.unicode { ['▁', '▔', '─', '▒'] } else { ['_', '-', '~', 'x'] };
+        levels[signal.code() as usize]
+    }
+
+    /// Values as segments starting with a bar, each labelled as far as it goes
+    fn vector(&self, samples: &[Vec<Signal>]) -> String {
+        let labels : Vec<String> = samples.iter().map(|value| hexadecimal(value)).collect();
+        let mut line = String::new();
+        let mut first = 0;
+        while first < labels.len() {
+            let end = (first..labels.len()).find(|c| labels[*c] != labels[first]).unwrap_or(labels.len());
+            let mut segment : Vec<char> = Vec::new();
+            if first > 0 {
+                segment.push(if self.unicode { '│' } else { '|' });
+            }
+            segment.extend(labels[first].chars());
+            segment.resize(end - first, ' ');
+            line.extend(segment);
+            first = end;
+        }
+        line
+    }
+}
+
+/// Start and end times on each side of the columns
+fn ruler(start: u64, end: u64, columns: usize) -> String {
+    let (start, end) = (format!("{} ps", start), format!("{} ps", end));
+    if start.len() + end.len() < columns {
+        format!("{}{:>width$}", start, end, width = columns - start.len())
+    } else {
+        format!("{} to {}", start, end)
+    }
+}
+
+/// Value of a vector, z when all its nets float and x when some are unknown
+pub(crate) fn hexadecimal(value: &[Signal]) -> String {
+    if value.iter().all(|signal| *signal == Signal::HIGH) {
+        return String::from("z");
+    }
+    if value.iter().any(|signal| *signal != Signal::ZERO && *signal != Signal::ONE) {
+        return String::from("x");
+    }
+    value.rchunks(4).rev()
+        .map(|nibble| nibble.iter().fold(0, |digit, signal| digit * 2 + (*signal == Signal::ONE) as u32))
+        .map(|digit| std::char::from_digit(digit, 16).unwrap().to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::delay::Delay;
+    use crate::render::Render;
+    use crate::vcd::Waveform;
+
+    #[test]
+    fn draw_test() {
+        let waveform = Waveform::parse("$timescale 1 ns $end\n\
+            $scope module caemu $end\n$var wire 1 ! clk $end\n$var wire 12 \" d [11:0] $end\n$upscope $end\n\
+            $enddefinitions $end\n#0 0! b10010 \"\n#3 1!\n#6 0! b10011 \"\n#9 1!\n#12 0! bx \"\n#15 1!\n").unwrap();
+        let render = Render::new().window(Delay::from_nanos(16)).columns(16);
+        assert_eq!(render.draw(&waveform, &["caemu.clk", "caemu.d", "caemu.q"], Delay::from_nanos(15)),
+            "time      0 ps    15000 ps\n\
+             caemu.clk ___---___---___-\n\
+             caemu.d   012   |013  |x\n\
+             caemu.q   not in the log\n");
+        assert_eq!(render.columns(4).unicode().draw(&waveform, &["caemu.clk"], Delay::from_nanos(3)),
+            "time      0 ps to 3000 ps\n\
+             caemu.clk ▁▁▁▔\n");
+    }
+}
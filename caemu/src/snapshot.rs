@@ -0,0 +1,206 @@
+use crate::bus::Signal;
+use crate::delay::Delay;
+use crate::error::{Error, Result};
+
+use std::convert::TryInto;
+
+/// State saved as bytes and restored later on, in place: a value is restored into
+/// one built the same way as the saved one (same board, same component).
+/// `#[derive(Snapshot)]` implements it field by field, `#[comp(snapshot)]` saves
+/// the fields of a component that aren't pins.
+pub trait Snapshot {
+    fn save(&self, out: &mut Vec<u8>);
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()>;
+}
+
+/// Takes the next `len` bytes of a snapshot
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(invalid("truncated"));
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+pub(crate) fn invalid(reason: &str) -> Error {
+    Error::Snapshot(String::from(reason))
+}
+
+macro_rules! snapshot_int {
+    ($($t:ty),*) => {
+        $(
+            impl Snapshot for $t {
+                fn save(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+                    let bytes = take(input, std::mem::size_of::<$t>())?;
+                    *self = <$t>::from_le_bytes(bytes.try_into().unwrap());
+                    Ok(())
+                }
+            }
+        )*
+    }
+}
+
+snapshot_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Snapshot for usize {
+    fn save(&self, out: &mut Vec<u8>) {
+        (*self as u64).save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let mut value = 0u64;
+        value.restore(input)?;
+        *self = value.try_into().map_err(|_| invalid("size out of range"))?;
+        Ok(())
+    }
+}
+
+impl Snapshot for bool {
+    fn save(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        *self = match take(input, 1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("bad boolean"))
+        };
+        Ok(())
+    }
+}
+
+impl Snapshot for Signal {
+    fn save(&self, out: &mut Vec<u8>) {
+        out.push(self.code());
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        *self = Signal::from_code(take(input, 1)?[0]).ok_or_else(|| invalid("bad signal"))?;
+        Ok(())
+    }
+}
+
+impl Snapshot for Delay {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.picoseconds.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.picoseconds.restore(input)
+    }
+}
+
+impl Snapshot for String {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.len().save(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let mut len = 0usize;
+        len.restore(input)?;
+        let bytes = take(input, len)?;
+        *self = String::from(std::str::from_utf8(bytes).map_err(|_| invalid("bad string"))?);
+        Ok(())
+    }
+}
+
+impl <T: Snapshot + Default> Snapshot for Option<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.is_some().save(out);
+        if let Some(value) = self {
+            value.save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let mut some = false;
+        some.restore(input)?;
+        if some {
+            self.get_or_insert_with(T::default).restore(input)
+        } else {
+            *self = None;
+            Ok(())
+        }
+    }
+}
+
+impl <T: Snapshot + Default> Snapshot for Vec<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.len().save(out);
+        for item in self.iter() {
+            item.save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        let mut len = 0usize;
+        len.restore(input)?;
+        // every item takes at least a byte, which bounds what a corrupted length allocates
+        if len > input.len() {
+            return Err(invalid("truncated"));
+        }
+        self.resize_with(len, T::default);
+        for item in self.iter_mut() {
+            item.restore(input)?;
+        }
+        Ok(())
+    }
+}
+
+impl <T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn save(&self, out: &mut Vec<u8>) {
+        for item in self.iter() {
+            item.save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        for item in self.iter_mut() {
+            item.restore(input)?;
+        }
+        Ok(())
+    }
+}
+
+impl <A: Snapshot, B: Snapshot> Snapshot for (A, B) {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.0.save(out);
+        self.1.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.0.restore(input)?;
+        self.1.restore(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Signal;
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn round_trip_test() {
+        let saved = (vec![(Delay::from_nanos(5), Signal::HIGH)], (Some(String::from("ram")), [7u16, 300]));
+        let mut out = Vec::new();
+        saved.save(&mut out);
+
+        let mut restored = (Vec::new(), (None, [0u16; 2]));
+        let mut input = &out[..];
+        restored.restore(&mut input).unwrap();
+        assert_eq!(restored, saved);
+        assert!(input.is_empty());
+
+        let mut input = &out[..out.len() - 1];
+        assert!(matches!(restored.restore(&mut input), Err(Error::Snapshot(_))));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::board::{CompleteBoard, WiredBoard};
+use crate::bus::{Bus, Signal};
+use crate::component::{Component, Connect, Out, PinKind};
+use crate::delay::Delay;
+use crate::error::{Error, Result};
+use crate::snapshot::Snapshot;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// Inside end of an exposed pin: drives the net with what the parent board drives on it
+pub(crate) struct Port {
+    name: String,
+    value: Signal,
+    out: Out,
+    bus: Option<Rc<RefCell<Bus>>>
+}
+
+impl Port {
+    pub(crate) fn new(name: &str) -> Rc<RefCell<Self>> {
+        Rc::from(RefCell::from(Port{name: String::from(name), value: Signal::HIGH, out: Out::new(1), bus: None}))
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn set(&mut self, value: Signal) {
+        if value != self.value {
+            self.value = value;
+            if let Some(bus) = &self.bus {
+                bus.borrow().wake();
+            }
+        }
+    }
+}
+
+impl Connect for Port {
+    fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+        self.out.connect(bus.clone());
+        self.bus = Some(bus);
+    }
+
+    fn get_name(&self, _id: usize) -> Result<String> {
+        Ok(self.name.clone())
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::InOut)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+}
+
+impl Snapshot for Port {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.value.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.value.restore(input)
+    }
+}
+
+impl Component for Port {
+    fn eval(&mut self) -> Delay {
+        self.out.set(self.value);
+        Delay::no_delay()
+    }
+}
+
+/// A board plugged into another one like a chip, its pins being the nets exposed
+/// with `Board::expose`. Each side sees what the other one drives on these nets,
+/// pulls stay on their own side.
+pub struct SubBoard {
+    board: CompleteBoard,
+    bus: Option<Rc<RefCell<Bus>>>,
+    error: Option<Error>
+}
+
+impl SubBoard {
+    /// Completes the board, whose pins are numbered from 1 in the order they were exposed
+    pub fn new(board: WiredBoard) -> Rc<RefCell<Self>> {
+        Rc::from(RefCell::from(SubBoard{board: board.complete_unlogged(), bus: None, error: None}))
+    }
+
+    pub fn board(&self) -> &CompleteBoard {
+        &self.board
+    }
+
+    pub fn board_mut(&mut self) -> &mut CompleteBoard {
+        &mut self.board
+    }
+
+    fn run(&mut self, bus: &RefCell<Bus>) -> Result<()> {
+        // catch up with the parent board, then apply what it drives at that time
+        let now = bus.borrow().now();
+        self.board.move_time(now)?;
+        for i in 0..self.board.ports_len() {
+            self.board.set_port(i, bus.borrow().driven_by_others(i));
+        }
+        self.board.run_until(now)?;
+        for i in 0..self.board.ports_len() {
+            bus.borrow_mut().set(i, self.board.port_value(i));
+        }
+        Ok(())
+    }
+}
+
+impl Connect for SubBoard {
+    fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+        self.bus = Some(bus);
+    }
+
+    fn get_name(&self, id: usize) -> Result<String> {
+        match id {
+            0 => None,
+            id => self.board.port_name(id - 1)
+        }.ok_or(Error::UnknownPin{component: String::from("SubBoard"), pin: id})
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(self.board.ports_len())
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::InOut)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+}
+
+/// The state of the inner board. A restore clears the error, the inner board
+/// running again from the restored state.
+impl Snapshot for SubBoard {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.board.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.error = None;
+        self.board.restore(input)
+    }
+}
+
+impl Component for SubBoard {
+    fn eval(&mut self) -> Delay {
+        if let (Some(bus), None) = (self.bus.clone(), &self.error) {
+            if let Err(error) = self.run(&bus) {
+                self.error = Some(error);
+            }
+        }
+        Delay::no_delay()
+    }
+
+    fn wakeups(&mut self, _now: Delay) -> Vec<Delay> {
+        match self.error {
+            None => self.board.next_event().into_iter().collect(),
+            Some(_) => Vec::new()
+        }
+    }
+
+    fn board(&self) -> Option<&CompleteBoard> {
+        Some(&self.board)
+    }
+
+    /// First error of the inner simulation, which then stops
+    fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::tests::{Buffer, Loop};
+    use crate::board::{Board, CompleteBoard};
+    use crate::bus::Signal;
+    use crate::component::{Component, Connect, In, Out};
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::subboard::SubBoard;
+    use crate::tester::{ProbeInput, ProbeOutput};
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    #[test]
+    fn subboard_test() {
+        // card: two buffers in a row
+        let mut card = Board::new();
+        let socket_first = card.socket(2);
+        let socket_second = card.socket(2);
+        socket_first.pin(2).unwrap().connect(&socket_second.pin(1).unwrap());
+        card.expose(&socket_first.pin(1).unwrap(), "a");
+        card.expose(&socket_second.pin(2).unwrap(), "y");
+        let mut card = card.wire();
+        card.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_first).unwrap();
+        card.plug(Rc::new(RefCell::new(Buffer{input: In::new(1), output: Out::new(2)}))).into(socket_second).unwrap();
+        let card = SubBoard::new(card);
+        assert_eq!(card.borrow().get_name(2), Ok(String::from("y")));
+
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_card = board.socket(2);
+        let socket_output = board.socket(1);
+        socket_card.name("card");
+        socket_input.pin(1).unwrap().connect(&socket_card.pin(1).unwrap());
+        socket_card.pin(2).unwrap().connect(&socket_output.pin(1).unwrap());
+        let mut board = board.wire();
+        let input = ProbeInput::new();
+        let output = ProbeOutput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(card.clone()).into(socket_card).unwrap();
+        board.plug(output.clone()).into(socket_output).unwrap();
+        let mut board = board.complete();
+
+        input.borrow_mut().set(Signal::ONE);
+        board.run_for(Delay::from_nanos(15)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::UNKNOWN);
+        board.run_for(Delay::from_nanos(10)).unwrap();
+        assert_eq!(output.borrow().get(), Signal::ONE);
+        assert_eq!(card.borrow().board().time(), Delay::from_nanos(20));
+
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+        assert_eq!(output.borrow().get(), Signal::ZERO);
+        assert_eq!(board.time(), Delay::from_nanos(45));
+        assert!(card.borrow().error().is_none());
+
+        // the card is logged in place of its socket, with its nets and sockets
+        let scope = board.scope("caemu");
+        assert_eq!(scope.children.len(), 3);
+        assert_eq!(scope.children[1].name, "card");
+        assert_eq!(scope.children[1].vars.len(), 3);
+        assert_eq!(scope.children[1].vars[0].nets, vec![2]);
+        assert_eq!(scope.children[1].children.len(), 2);
+    }
+
+    /// A card with a loop, enabled by the input of the board it is plugged into
+    fn looping_board() -> (CompleteBoard, Rc<RefCell<ProbeInput>>, Rc<RefCell<SubBoard>>) {
+        let mut card = Board::new();
+        let socket_loop = card.socket(3);
+        socket_loop.pin(3).unwrap().connect(&socket_loop.pin(2).unwrap());
+        card.expose(&socket_loop.pin(1).unwrap(), "enable");
+        let mut card = card.wire();
+        card.plug(Rc::new(RefCell::new(Loop{enable: In::new(1), feedback: In::new(2), output: Out::new(3)}))).into(socket_loop).unwrap();
+        let card = SubBoard::new(card);
+
+        let mut board = Board::new();
+        let socket_input = board.socket(1);
+        let socket_card = board.socket(1);
+        socket_input.pin(1).unwrap().connect(&socket_card.pin(1).unwrap());
+        let mut board = board.wire();
+        let input = ProbeInput::new();
+        board.plug(input.clone()).into(socket_input).unwrap();
+        board.plug(card.clone()).into(socket_card).unwrap();
+        let mut board = board.complete_unlogged();
+        input.borrow_mut().set(Signal::ZERO);
+        board.eval().unwrap();
+        (board, input, card)
+    }
+
+    #[test]
+    fn error_test() {
+        // the loop inside the card stops the board it is plugged into
+        let (mut board, input, card) = looping_board();
+        input.borrow_mut().set(Signal::ONE);
+        let error = board.eval().unwrap_err();
+        assert!(matches!(error, Error::Oscillation(_)));
+        assert_eq!(card.borrow().error(), Some(&error));
+
+        // and so does a step
+        let (mut board, input, _) = looping_board();
+        input.borrow_mut().set(Signal::ONE);
+        assert!(matches!(board.step(), Err(Error::Oscillation(_))));
+    }
+}
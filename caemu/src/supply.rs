@@ -0,0 +1,148 @@
+use crate::bus::{Bus, Signal};
+use crate::component::{Component, Connect, Out, PinKind};
+use crate::delay::Delay;
+use crate::error::Result;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// Positive supply rail: ZERO until it powers on, ONE afterwards
+pub struct Vcc {
+    out: Out,
+    on: Delay,
+    bus: Option<Rc<RefCell<Bus>>>
+}
+
+/// Ground rail, always ZERO
+pub struct Gnd {
+    out: Out
+}
+
+impl Vcc {
+    /// Rail up from time 0
+    pub fn new() -> Rc<RefCell<Self>> {
+        Rc::from(RefCell::from(Vcc{out: Out::new(1), on: Delay::no_delay(), bus: None}))
+    }
+
+    /// Time at which the rail comes up, to simulate power-on sequencing
+    pub fn set_power_on(&mut self, on: Delay) {
+        self.on = on;
+    }
+}
+
+impl Gnd {
+    pub fn new() -> Rc<RefCell<Self>> {
+        Rc::from(RefCell::from(Gnd{out: Out::new(1)}))
+    }
+}
+
+impl Vcc {
+    fn now(&self) -> Delay {
+        self.bus.as_ref().map(|bus| bus.borrow().now()).unwrap_or_default()
+    }
+}
+
+impl Connect for Vcc {
+    fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+        self.out.connect(bus.clone());
+        self.bus = Some(bus);
+    }
+
+    fn get_name(&self, _id: usize) -> Result<String> {
+        Ok(String::from("vcc"))
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::Supply)
+    }
+}
+
+impl Connect for Gnd {
+    fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
+        self.out.connect(bus);
+    }
+
+    fn get_name(&self, _id: usize) -> Result<String> {
+        Ok(String::from("gnd"))
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::Supply)
+    }
+}
+
+impl Component for Vcc {
+    fn eval(&mut self) -> Delay {
+        // the level only depends on the time, however often the rail gets evaluated
+        self.out.set(if self.now() < self.on { Signal::ZERO } else { Signal::ONE });
+        Delay::no_delay()
+    }
+
+    fn wakeups(&mut self, now: Delay) -> Vec<Delay> {
+        if now < self.on {
+            vec![self.on]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Component for Gnd {
+    fn eval(&mut self) -> Delay {
+        self.out.set(Signal::ZERO);
+        Delay::no_delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::bus::Signal;
+    use crate::delay::Delay;
+    use crate::supply::{Vcc, Gnd};
+    use crate::tester::ProbeOutput;
+
+    #[test]
+    fn power_on_test() {
+        let mut board = Board::new();
+        let socket_vcc = board.socket(1);
+        let socket_gnd = board.socket(1);
+        let socket_vcc_probe = board.socket(1);
+        let socket_gnd_probe = board.socket(1);
+        socket_vcc.pin(1).unwrap().connect(&socket_vcc_probe.pin(1).unwrap());
+        socket_gnd.pin(1).unwrap().connect(&socket_gnd_probe.pin(1).unwrap());
+        let mut board = board.wire();
+
+        let vcc = Vcc::new();
+        vcc.borrow_mut().set_power_on(Delay::from_micros(1));
+        let vcc_probe = ProbeOutput::new();
+        let gnd_probe = ProbeOutput::new();
+        board.plug(vcc).into(socket_vcc).unwrap();
+        board.plug(Gnd::new()).into(socket_gnd).unwrap();
+        board.plug(vcc_probe.clone()).into(socket_vcc_probe).unwrap();
+        board.plug(gnd_probe.clone()).into(socket_gnd_probe).unwrap();
+        let mut board = board.complete();
+
+        board.run_until(Delay::from_nanos(500)).unwrap();
+        assert_eq!(vcc_probe.borrow().get(), Signal::ZERO);
+
+        // evaluated again before its time, the rail stays down
+        board.force_net("U1.vcc", Signal::ONE).unwrap();
+        board.release_net("U1.vcc").unwrap();
+        board.run_until(Delay::from_nanos(999)).unwrap();
+        assert_eq!(vcc_probe.borrow().get(), Signal::ZERO);
+        assert_eq!(gnd_probe.borrow().get(), Signal::ZERO);
+        board.eval().unwrap();
+        assert_eq!(board.time(), Delay::from_micros(1));
+        assert_eq!(vcc_probe.borrow().get(), Signal::ONE);
+        assert_eq!(gnd_probe.borrow().get(), Signal::ZERO);
+    }
+}
@@ -1,19 +1,28 @@
 use crate::board::{Board, CompleteBoard};
 use crate::bus::{Signal, Bus};
-use crate::component::{Component, In, Out, Connect};
+use crate::component::{Component, In, Out, Connect, PinKind};
 use crate::delay::Delay;
+use crate::error::Result;
+use crate::logger::Log;
+use crate::render::Render;
+use crate::snapshot::Snapshot;
+use crate::vcd::Recording;
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
 pub struct Tester {
     inputs: Vec<Rc<RefCell<ProbeInput>>>,
-    outputs: Vec<Rc<RefCell<ProbeOutput>>>
+    outputs: Vec<Rc<RefCell<ProbeOutput>>>,
+    recording: Option<Recording>,
+    watched: Vec<String>,
+    window: Delay
 }
 
 pub struct ProbeInput {
     value: Signal,
-    out: Out
+    out: Out,
+    bus: Option<Rc<RefCell<Bus>>>
 }
 
 pub struct ProbeOutput {
@@ -23,21 +32,47 @@ pub struct ProbeOutput {
 
 impl ProbeInput {
     pub fn new() -> Rc<RefCell<Self>> {
-        Rc::from(RefCell::from(ProbeInput{value: Signal::HIGH, out: Out::new(1)}))
+        Rc::from(RefCell::from(ProbeInput{value: Signal::HIGH, out: Out::new(1), bus: None}))
     }
 
     pub fn set(&mut self, value: Signal) {
         self.value = value;
+        if let Some(bus) = &self.bus {
+            bus.borrow().wake();
+        }
     }
 }
 
 impl Connect for ProbeInput {
     fn connect(&mut self, bus: Rc<RefCell<Bus>>) {
-        self.out.connect(bus);
+        self.out.connect(bus.clone());
+        self.bus = Some(bus);
+    }
+
+    fn get_name(&self, id: usize) -> Result<String> {
+        Ok(format!("i{}", id))
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::TriState)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+}
+
+impl Snapshot for ProbeInput {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.value.save(out);
     }
 
-    fn get_name(&self, id: usize) -> String {
-        format!("i{}", id)
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.value.restore(input)
     }
 }
 
@@ -63,8 +98,30 @@ impl Connect for ProbeOutput {
         self.input.connect(bus);
     }
 
-    fn get_name(&self, id: usize) -> String {
-        format!("o{}", id)
+    fn get_name(&self, id: usize) -> Result<String> {
+        Ok(format!("o{}", id))
+    }
+
+    fn pin_count(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn pin_kind(&self, _id: usize) -> Option<PinKind> {
+        Some(PinKind::Input)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+}
+
+impl Snapshot for ProbeOutput {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.value.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<()> {
+        self.value.restore(input)
     }
 }
 
@@ -87,7 +144,7 @@ impl Tester {
         for _ in 0..outputs_count {
             outputs.push(ProbeOutput::new())
         }
-        Tester{inputs, outputs}
+        Tester{inputs, outputs, recording: None, watched: Vec::new(), window: Delay::from_micros(1)}
     }
 
     pub fn from <T: Component + Connect + 'static> (
@@ -95,7 +152,7 @@ impl Tester {
         outputs: &[usize],
         component: Rc<RefCell<T>>,
         component_size: usize
-    ) -> (Self, CompleteBoard) {
+    ) -> Result<(Self, CompleteBoard)> {
         // create the board
         let mut board = Board::new();
 
@@ -105,13 +162,13 @@ impl Tester {
 
         for i in 0..inputs.len() {
             let socket = board.socket(1);
-            socket.pin(1).name(&format!("in{}", i));
+            socket.pin(1)?.name(&format!("in{}", i));
             socket_inputs.push(socket);
         }
 
         for i in 0..outputs.len() {
             let socket = board.socket(1);
-            socket.pin(1).name(&format!("out{}", i));
+            socket.pin(1)?.name(&format!("out{}", i));
             socket_outputs.push(socket);
         }
 
@@ -119,32 +176,39 @@ impl Tester {
 
         // wire the socket together
         for (i, socket) in socket_inputs.iter_mut().enumerate() {
-            socket.pin(1).connect(&component_socket.pin(inputs[i]));
+            socket.pin(1)?.connect(&component_socket.pin(inputs[i])?);
         }
         for (i, socket) in socket_outputs.iter_mut().enumerate() {
-            socket.pin(1).connect(&component_socket.pin(outputs[i]));
+            socket.pin(1)?.connect(&component_socket.pin(outputs[i])?);
         }
 
         // Wire the board: no more connection allowed
         let mut board = board.wire();
 
         // plug component
-        board.plug(component).into(component_socket);
+        board.plug(component).into(component_socket)?;
 
-        // Create tester + probes
-        let tester = Tester::new(inputs.len(), outputs.len());
+        // Create tester + probes, the nets they are on being drawn when a test fails
+        let mut tester = Tester::new(inputs.len(), outputs.len());
+        tester.watched.extend((0..inputs.len()).map(|i| format!("caemu.in{}", i)));
+        tester.watched.extend((0..outputs.len()).map(|i| format!("caemu.out{}", i)));
         for i in 0..inputs.len() {
             let input = tester.input(i);
             let input_socket = socket_inputs.remove(0);
-            board.plug(input).into(input_socket);
+            board.plug(input).into(input_socket)?;
         }
         for i in 0..outputs.len() {
             let output = tester.output(i);
             let output_socket = socket_outputs.remove(0);
-            board.plug(output).into(output_socket);
+            board.plug(output).into(output_socket)?;
         }
 
-        (tester, board.complete())
+        // only what can be drawn is kept
+        let recording = Recording::new();
+        recording.set_window(tester.window);
+        let board = board.complete_with_log(Log::Writer(Box::new(recording.clone())))?;
+        tester.recording = Some(recording);
+        Ok((tester, board))
     }
 
     pub fn input(&self, input_id: usize) -> Rc<RefCell<ProbeInput>> {
@@ -155,6 +219,19 @@ impl Tester {
         self.outputs.get(output_id).unwrap().clone()
     }
 
+    /// Also draws a variable of the log when a test fails, by full name such as `caemu.U1.a`
+    pub fn watch(&mut self, name: &str) {
+        self.watched.push(String::from(name));
+    }
+
+    /// Duration drawn when a test fails, until the time of the failure, 1 µs by default
+    pub fn set_window(&mut self, window: Delay) {
+        self.window = window;
+        if let Some(recording) = &self.recording {
+            recording.set_window(window);
+        }
+    }
+
     pub fn test(&mut self, board: &mut CompleteBoard, inputs: Vec<Signal>, outputs: Vec<Signal>) {
         assert_eq!(inputs.len(), self.inputs.len());
         assert_eq!(outputs.len(), self.outputs.len());
@@ -163,10 +240,26 @@ impl Tester {
             iter.0.borrow_mut().set(iter.1);
         }
 
-        board.eval();
+        if let Err(error) = board.eval() {
+            panic!("{}", error);
+        }
+
+        for (i, (output, expected)) in self.outputs.iter().zip(outputs).enumerate() {
+            let actual = output.borrow().get();
+            if actual != expected {
+                panic!("{}", self.failure(board, i, actual, expected));
+            }
+        }
+    }
 
-        for iter in self.outputs.iter().zip(outputs) {
-            assert_eq!(iter.0.borrow().get(), iter.1);
+    /// What went wrong, followed by the watched variables when the board is logged
+    fn failure(&self, board: &CompleteBoard, output: usize, actual: Signal, expected: Signal) -> String {
+        let mut message = format!("output {} is {:?} at {} ps, expected {:?}", output, actual, board.time().picoseconds, expected);
+        if let Some(waveform) = self.recording.as_ref().and_then(|recording| recording.waveform().ok()) {
+            let names : Vec<&str> = self.watched.iter().map(|name| name.as_str()).collect();
+            message.push('\n');
+            message.push_str(&Render::new().window(self.window).draw(&waveform, &names, board.time()));
         }
+        message
     }
 }
\ No newline at end of file
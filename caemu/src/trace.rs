@@ -0,0 +1,616 @@
+//! Binary trace of a simulation, far smaller than a VCD log and readable at any
+//! time without going through what comes before.
+//!
+//! Integers are LEB128 varints unless said otherwise. A trace is made of:
+//!
+//! - a header: `caemu trace 1\n`, the number of nets, then the name of each net
+//!   (length and UTF-8 bytes). Nets of the boards plugged as components are
+//!   prefixed by the name of their socket, such as `card.a`.
+//! - blocks of about 64 KiB before compression, each starting with its time in
+//!   picoseconds and a keyframe: the value of every net at the start of the block, 4 per byte,
+//!   2 bits each (0 ZERO, 1 ONE, 2 HIGH, 3 UNKNOWN), first net in the low bits.
+//!   Then records, one per logged time: the time since the previous record (or
+//!   the start of the block), the number of changed nets, and for each of them
+//!   in increasing order `(index - previous index) << 2 | value`, the previous
+//!   index starting at 0 for the first one.
+//!   A block is stored as its length, then compressed as a sequence of literal
+//!   bytes and copies: the number of literals and the literals, then, unless the
+//!   block ends there, the distance back to the copied bytes and their number
+//!   minus 4. A copy may overlap the bytes it produces.
+//! - the index: for each block its time, offset in the file and stored length,
+//!   as u64 little endian.
+//! - a footer: offset of the index and number of blocks as u64 little endian,
+//!   then `caemuidx`.
+//!
+//! Nets start UNKNOWN. The index is written when the trace is closed.
+
+use crate::bus::Signal;
+use crate::delay::Delay;
+use crate::error::{Error, Result};
+use crate::logger::{Scope, Sink};
+use crate::vcd::Waveform;
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"caemu trace 1\n";
+const FOOTER_MAGIC: &[u8] = b"caemuidx";
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Where a block starts, in time and in the file
+#[derive(Clone, Copy)]
+struct BlockIndex {
+    time: u64,
+    offset: u64,
+    len: u64
+}
+
+/// A block read back
+struct Block {
+    time: u64,
+    keyframe: Vec<Signal>,
+    records: Vec<(u64, Vec<(usize, Signal)>)>
+}
+
+/// Writes the value changes of the nets as a binary trace
+pub struct TraceWriter {
+    out: Box<dyn Write>,
+    offset: u64,
+    values: Vec<Signal>,
+    block: Vec<u8>,
+    block_time: u64,
+    last_time: u64,
+    index: Vec<BlockIndex>,
+    closed: bool,
+    error: Option<Error>
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = input.split_first().ok_or_else(|| corrupted("truncated"))?;
+        *input = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(corrupted("varint too long"))
+}
+
+fn corrupted(reason: &str) -> Error {
+    Error::Trace(String::from(reason))
+}
+
+// shortest copy worth it, and the bits of the hash of the bytes it starts with
+const MIN_COPY: usize = 4;
+const HASH_BITS: u32 = 12;
+
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_varint(&mut out, input.len() as u64);
+    // last position of the hashed bytes, plus one
+    let mut positions = vec![0usize; 1 << HASH_BITS];
+    let (mut literals, mut i) = (0, 0);
+    while i + MIN_COPY <= input.len() {
+        let bytes = u32::from_le_bytes(input[i..i + MIN_COPY].try_into().unwrap());
+        let hash = (bytes.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = positions[hash];
+        positions[hash] = i + 1;
+        if candidate == 0 || input[candidate - 1..candidate - 1 + MIN_COPY] != input[i..i + MIN_COPY] {
+            i += 1;
+            continue;
+        }
+        let from = candidate - 1;
+        let len = input[i..].iter().zip(input[from..].iter()).take_while(|(a, b)| a == b).count();
+        push_varint(&mut out, (i - literals) as u64);
+        out.extend_from_slice(&input[literals..i]);
+        push_varint(&mut out, (i - from) as u64);
+        push_varint(&mut out, (len - MIN_COPY) as u64);
+        i += len;
+        literals = i;
+    }
+    push_varint(&mut out, (input.len() - literals) as u64);
+    out.extend_from_slice(&input[literals..]);
+    out
+}
+
+fn decompress(mut input: &[u8]) -> Result<Vec<u8>> {
+    let len = read_varint(&mut input)? as usize;
+    let mut out = Vec::with_capacity(len.min(input.len().saturating_mul(256)));
+    loop {
+        let literals = read_varint(&mut input)? as usize;
+        if literals > input.len() || out.len() + literals > len {
+            return Err(corrupted("bad block"));
+        }
+        out.extend_from_slice(&input[..literals]);
+        input = &input[literals..];
+        if input.is_empty() {
+            break;
+        }
+        let distance = read_varint(&mut input)? as usize;
+        let copied = (read_varint(&mut input)? as usize).saturating_add(MIN_COPY);
+        if distance == 0 || distance > out.len() || copied > len - out.len() {
+            return Err(corrupted("bad block"));
+        }
+        let from = out.len() - distance;
+        for i in from..from + copied {
+            out.push(out[i]);
+        }
+    }
+    if out.len() != len {
+        return Err(corrupted("bad block"));
+    }
+    Ok(out)
+}
+
+// value in the low 2 bits
+fn signal(code: u64) -> Signal {
+    Signal::from_code((code & 3) as u8).unwrap()
+}
+
+/// Name of each logged value: the nets of the board, then the ones of its sub-boards
+pub(crate) fn net_names(scope: &Scope) -> Vec<String> {
+    let mut names = Vec::new();
+    collect_names(scope, "", &mut names, false);
+    collect_names(scope, "", &mut names, true);
+    names.into_iter().enumerate().map(|(i, name)| name.unwrap_or_else(|| format!("B{}", i))).collect()
+}
+
+// board scopes list their nets before the sockets, which only alias them, and the
+// bits of vectors are only named after the nets of no single variable
+fn collect_names(scope: &Scope, prefix: &str, names: &mut Vec<Option<String>>, vectors: bool) {
+    for var in scope.vars.iter().filter(|var| (var.nets.len() > 1) == vectors) {
+        for (bit, net) in var.nets.iter().rev().enumerate() {
+            if names.len() <= *net {
+                names.resize(net + 1, None);
+            }
+            names[*net].get_or_insert_with(|| if vectors {
+                format!("{}{}[{}]", prefix, var.name, bit)
+            } else {
+                format!("{}{}", prefix, var.name)
+            });
+        }
+    }
+    for child in scope.children.iter() {
+        collect_names(child, &format!("{}{}.", prefix, child.name), names, vectors);
+    }
+}
+
+impl TraceWriter {
+    /// Traces the nets of a scope and of its children
+    pub fn new(scope: &Scope, out: Box<dyn Write>) -> Self {
+        let names = net_names(scope);
+        let mut writer = TraceWriter {
+            out,
+            offset: 0,
+            values: vec![Signal::UNKNOWN; names.len()],
+            block: Vec::new(),
+            block_time: 0,
+            last_time: 0,
+            index: Vec::new(),
+            closed: false,
+            error: None
+        };
+        let mut header = Vec::from(MAGIC);
+        push_varint(&mut header, names.len() as u64);
+        for name in names.iter() {
+            push_varint(&mut header, name.len() as u64);
+            header.extend_from_slice(name.as_bytes());
+        }
+        writer.write(&header);
+        writer
+    }
+
+    /// Traces to a file, created or truncated
+    pub fn create<P: AsRef<Path>>(scope: &Scope, path: P) -> Result<Self> {
+        let file = File::create(path.as_ref()).map_err(|e| Error::io(path.as_ref(), e))?;
+        Ok(TraceWriter::new(scope, Box::new(std::io::BufWriter::new(file))))
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            match self.out.write_all(bytes) {
+                Ok(()) => self.offset += bytes.len() as u64,
+                Err(error) => self.error = Some(Error::from(error))
+            }
+        }
+    }
+
+    fn start_block(&mut self, time: u64) {
+        self.block_time = time;
+        self.last_time = time;
+        push_varint(&mut self.block, time);
+        let mut keyframe = vec![0u8; self.values.len().div_ceil(4)];
+        for (i, value) in self.values.iter().enumerate() {
+            keyframe[i / 4] |= value.code() << (2 * (i % 4));
+        }
+        self.block.extend_from_slice(&keyframe);
+    }
+
+    fn end_block(&mut self) {
+        if !self.block.is_empty() {
+            let block = compress(&std::mem::take(&mut self.block));
+            self.index.push(BlockIndex{time: self.block_time, offset: self.offset, len: block.len() as u64});
+            self.write(&block);
+        }
+    }
+}
+
+impl Sink for TraceWriter {
+    fn log(&mut self, signals: &[Signal], time: &Delay) {
+        if self.closed {
+            return;
+        }
+        let changed : Vec<usize> = (0..self.values.len().min(signals.len()))
+            .filter(|i| signals[*i] != self.values[*i])
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+        let time = time.picoseconds;
+        // a block only holds times at or after its start, a rewound board starts a new one
+        if self.block.len() >= BLOCK_SIZE || (!self.block.is_empty() && time < self.last_time) {
+            self.end_block();
+        }
+        if self.block.is_empty() {
+            self.start_block(time);
+        }
+        push_varint(&mut self.block, time - self.last_time);
+        push_varint(&mut self.block, changed.len() as u64);
+        let mut previous = 0;
+        for net in changed {
+            push_varint(&mut self.block, (((net - previous) as u64) << 2) | signals[net].code() as u64);
+            previous = net;
+            self.values[net] = signals[net];
+        }
+        self.last_time = time;
+    }
+
+    fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if !self.closed {
+            self.closed = true;
+            self.end_block();
+            let index_offset = self.offset;
+            let mut footer = Vec::new();
+            for block in self.index.iter() {
+                footer.extend_from_slice(&block.time.to_le_bytes());
+                footer.extend_from_slice(&block.offset.to_le_bytes());
+                footer.extend_from_slice(&block.len.to_le_bytes());
+            }
+            footer.extend_from_slice(&index_offset.to_le_bytes());
+            footer.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
+            footer.extend_from_slice(FOOTER_MAGIC);
+            self.write(&footer);
+            if let (Err(error), None) = (self.out.flush(), &self.error) {
+                self.error = Some(Error::from(error));
+            }
+        }
+        match &self.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(())
+        }
+    }
+}
+
+/// The index gets written even if the trace isn't closed explicitly
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Reads a trace, one block at a time
+pub struct TraceReader<R: Read + Seek> {
+    input: R,
+    names: Vec<String>,
+    index: Vec<BlockIndex>
+}
+
+impl TraceReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref()).map_err(|e| Error::io(path.as_ref(), e))?;
+        TraceReader::new(BufReader::new(file))
+    }
+}
+
+impl <R: Read + Seek> TraceReader<R> {
+    /// Reads the header and the index of a closed trace
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut footer = [0u8; 24];
+        let len = input.seek(SeekFrom::End(0))?;
+        if len < 24 {
+            return Err(corrupted("too short"));
+        }
+        input.seek(SeekFrom::End(-24))?;
+        input.read_exact(&mut footer)?;
+        if &footer[16..] != FOOTER_MAGIC {
+            return Err(corrupted("no index, the trace wasn't closed"));
+        }
+        let index_offset = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let count = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+        // the index fills the file up to the footer
+        if index_offset == 0 || count.checked_mul(24).and_then(|l| l.checked_add(index_offset)) != Some(len - 24) {
+            return Err(corrupted("bad index"));
+        }
+        let mut index_bytes = vec![0u8; (count * 24) as usize];
+        input.seek(SeekFrom::Start(index_offset))?;
+        input.read_exact(&mut index_bytes)?;
+        let index : Vec<BlockIndex> = index_bytes.chunks(24).map(|entry| BlockIndex {
+            time: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+            len: u64::from_le_bytes(entry[16..].try_into().unwrap())
+        }).collect();
+        if index.iter().any(|b| b.offset.checked_add(b.len).is_none_or(|end| end > index_offset)) {
+            return Err(corrupted("bad index"));
+        }
+
+        // the header ends where the first block starts, or where the index does
+        let header_len = index.first().map_or(index_offset, |b| b.offset);
+        let mut header = vec![0u8; header_len as usize];
+        input.seek(SeekFrom::Start(0))?;
+        input.read_exact(&mut header)?;
+        let mut bytes = &header[..];
+        if !bytes.starts_with(MAGIC) {
+            return Err(corrupted("not a caemu trace"));
+        }
+        bytes = &bytes[MAGIC.len()..];
+        let count = read_varint(&mut bytes)?;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            let len = read_varint(&mut bytes)? as usize;
+            if bytes.len() < len {
+                return Err(corrupted("truncated"));
+            }
+            let (name, rest) = bytes.split_at(len);
+            names.push(String::from_utf8(name.to_vec()).map_err(|_| corrupted("bad name"))?);
+            bytes = rest;
+        }
+        Ok(TraceReader{input, names, index})
+    }
+
+    /// Names of the nets, in the order of their values
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn net(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Value of every net at a time, once the changes at that time are applied
+    pub fn values_at(&mut self, time: Delay) -> Result<Vec<Signal>> {
+        // the last block starting before, blocks of a rewound board start earlier
+        let block = match self.index.iter().rposition(|b| b.time <= time.picoseconds) {
+            Some(block) => self.read_block(self.index[block])?,
+            None => return Ok(vec![Signal::UNKNOWN; self.names.len()])
+        };
+        let mut values = block.keyframe;
+        for (_, changes) in block.records.iter().take_while(|(at, _)| *at <= time.picoseconds) {
+            for (net, value) in changes.iter() {
+                values[*net] = *value;
+            }
+        }
+        Ok(values)
+    }
+
+    /// Changes of every net between two times, to decode them
+    pub fn waveform(&mut self, start: Delay, end: Delay) -> Result<Waveform> {
+        let mut changes : Vec<Vec<(Delay, Vec<Signal>)>> = vec![Vec::new(); self.names.len()];
+        let first = self.index.iter().rposition(|b| b.time <= start.picoseconds).unwrap_or(0);
+        for k in first..self.index.len() {
+            let block = self.index[k];
+            if block.time > end.picoseconds {
+                continue;
+            }
+            let block = self.read_block(block)?;
+            let mut values = block.keyframe;
+            let mut later = Vec::new();
+            for (at, list) in block.records.into_iter().take_while(|(at, _)| *at <= end.picoseconds) {
+                if at <= start.picoseconds {
+                    for (net, value) in list {
+                        values[net] = value;
+                    }
+                } else {
+                    later.push((Delay::from_picos(at), list));
+                }
+            }
+            // what a rewound board did after the start of the block is forgotten
+            let time = Delay::from_picos(block.time.max(start.picoseconds));
+            for (net, value) in values.into_iter().enumerate() {
+                changes[net].retain(|(at, _)| *at < time);
+                changes[net].push((time, vec![value]));
+            }
+            for (at, list) in later {
+                for (net, value) in list {
+                    changes[net].push((at, vec![value]));
+                }
+            }
+        }
+        Ok(Waveform::from_nets(&self.names, changes))
+    }
+
+    /// Keyframe and records of a block, with the time of each record
+    fn read_block(&mut self, block: BlockIndex) -> Result<Block> {
+        let mut bytes = vec![0u8; block.len as usize];
+        self.input.seek(SeekFrom::Start(block.offset))?;
+        self.input.read_exact(&mut bytes)?;
+        let bytes = decompress(&bytes)?;
+        let mut input = &bytes[..];
+        let time = read_varint(&mut input)?;
+        let keyframe_len = self.names.len().div_ceil(4);
+        if input.len() < keyframe_len {
+            return Err(corrupted("truncated"));
+        }
+        let keyframe = (0..self.names.len()).map(|i| signal((input[i / 4] >> (2 * (i % 4))) as u64)).collect();
+        input = &input[keyframe_len..];
+        let mut records = Vec::new();
+        let mut now = time;
+        while !input.is_empty() {
+            now += read_varint(&mut input)?;
+            let count = read_varint(&mut input)?;
+            let mut changes = Vec::new();
+            let mut net = 0usize;
+            for _ in 0..count {
+                let change = read_varint(&mut input)?;
+                net += (change >> 2) as usize;
+                if net >= self.names.len() {
+                    return Err(corrupted("no such net"));
+                }
+                changes.push((net, signal(change)));
+            }
+            records.push((now, changes));
+        }
+        Ok(Block{time, keyframe, records})
+    }
+
+    /// Value of a net at a time, found by name
+    pub fn value(&mut self, name: &str, time: Delay) -> Result<Signal> {
+        let net = self.net(name).ok_or_else(|| Error::UnknownNet{name: String::from(name)})?;
+        Ok(self.values_at(time)?[net])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::bus::Signal;
+    use crate::clock::Clock;
+    use crate::decode::{Decoder, Transaction, Uart};
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::logger::{Log, Logger, Scope, Sink, Var};
+    use crate::tester::ProbeOutput;
+    use crate::trace::{compress, decompress, TraceReader, TraceWriter};
+    use crate::vcd::Recording;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn seek_test() {
+        let card = Scope{name: String::from("card"), vars: vec![Var{name: String::from("a"), nets: vec![2]}], children: Vec::new()};
+        let vars = (0..2).map(|net| Var{name: format!("n{}", net), nets: vec![net]}).collect();
+        let scope = Scope{name: String::from("caemu"), vars, children: vec![card]};
+        let out = Recording::new();
+        let mut writer = TraceWriter::new(&scope, Box::new(out.clone()));
+        let vcd = Recording::new();
+        let mut logger = Logger::new(&scope, Box::new(vcd.clone()));
+
+        // a counter on two nets, enough changes for several blocks
+        for time in 0..100_000u64 {
+            let bit = |b: u64| if time >> b & 1 == 1 { Signal::ONE } else { Signal::ZERO };
+            writer.log(&[bit(0), bit(1), Signal::HIGH], &Delay::from_nanos(time));
+            logger.log(&[bit(0), bit(1), Signal::HIGH], &Delay::from_nanos(time));
+        }
+        writer.close().unwrap();
+        let trace = out.bytes();
+        assert!(trace.len() * 100 < vcd.bytes().len());
+
+        let mut reader = TraceReader::new(Cursor::new(trace)).unwrap();
+        assert_eq!(reader.names(), &["n0", "n1", "card.a"]);
+        assert!(reader.index.len() > 1);
+        for time in [0u64, 1, 6, 54_321, 99_999] {
+            let values = reader.values_at(Delay::from_nanos(time)).unwrap();
+            assert_eq!(values[0] == Signal::ONE, time & 1 == 1, "at {} ns", time);
+            assert_eq!(values[1] == Signal::ONE, time & 2 == 2, "at {} ns", time);
+        }
+        assert_eq!(reader.value("n1", Delay::from_picos(2_002_500)), Ok(Signal::ONE));
+        assert_eq!(reader.value("card.a", Delay::from_nanos(7)), Ok(Signal::HIGH));
+        assert!(reader.value("n2", Delay::no_delay()).is_err());
+    }
+
+    #[test]
+    fn waveform_test() {
+        let scope = Scope{name: String::from("caemu"), vars: vec![Var{name: String::from("tx"), nets: vec![0]}], children: Vec::new()};
+        let out = Recording::new();
+        let mut writer = TraceWriter::new(&scope, Box::new(out.clone()));
+        // 'A' at 1 Mbaud, then a board rewound to 15 µs
+        for (time, value) in [(0, 1), (1, 0), (2, 1), (3, 0), (8, 1), (9, 0), (10, 1), (20, 0), (15, 1)] {
+            let value = if value == 1 { Signal::ONE } else { Signal::ZERO };
+            writer.log(&[value], &Delay::from_micros(time));
+        }
+        writer.close().unwrap();
+        let mut reader = TraceReader::new(Cursor::new(out.bytes())).unwrap();
+
+        let waveform = reader.waveform(Delay::no_delay(), Delay::from_micros(30)).unwrap();
+        assert_eq!(Uart::new("tx", 1_000_000).decode(&waveform).unwrap(),
+            vec![Transaction{start: Delay::from_micros(1), end: Delay::from_micros(11), text: String::from("41 'A'")}]);
+        assert_eq!(waveform.changes("tx").unwrap().last(), Some(&(Delay::from_micros(10), vec![Signal::ONE])));
+        let waveform = reader.waveform(Delay::from_micros(5), Delay::from_nanos(8_500)).unwrap();
+        assert_eq!(waveform.changes("tx").unwrap(), vec![(Delay::from_micros(5), vec![Signal::ZERO]), (Delay::from_micros(8), vec![Signal::ONE])]);
+    }
+
+    #[test]
+    fn compress_test() {
+        let mut seed = 1u32;
+        let mut bytes : Vec<u8> = (0..5000).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 24) as u8
+        }).collect();
+        // a copy overlapping what it produces, and one from far back
+        bytes.extend_from_slice(&[7; 1000]);
+        bytes.extend_from_within(100..600);
+        let compressed = compress(&bytes);
+        assert!(compressed.len() < bytes.len() - 1000);
+        assert_eq!(decompress(&compressed), Ok(bytes));
+        assert!(decompress(&compressed[..compressed.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn corrupted_test() {
+        let scope = Scope{name: String::from("caemu"), vars: vec![Var{name: String::from("a"), nets: vec![0]}], children: Vec::new()};
+        let out = Recording::new();
+        let mut writer = TraceWriter::new(&scope, Box::new(out.clone()));
+        writer.log(&[Signal::ONE], &Delay::from_nanos(1));
+        writer.close().unwrap();
+        let trace = out.bytes();
+        assert!(TraceReader::new(Cursor::new(trace.clone())).is_ok());
+
+        let bad_index = Some(Error::Trace(String::from("bad index")));
+        let mut huge = trace.clone();
+        let count = huge.len() - 16;
+        huge[count..count + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(TraceReader::new(Cursor::new(huge)).err(), bad_index.clone());
+        let mut truncated = trace[..20].to_vec();
+        truncated.extend_from_slice(&trace[trace.len() - 24..]);
+        assert_eq!(TraceReader::new(Cursor::new(truncated)).err(), bad_index);
+        assert_eq!(TraceReader::new(Cursor::new(&trace[..trace.len() - 1])).err(),
+            Some(Error::Trace(String::from("no index, the trace wasn't closed"))));
+    }
+
+    #[test]
+    fn board_test() {
+        let mut board = Board::new();
+        let socket_clock = board.socket(1);
+        let socket_probe = board.socket(1);
+        socket_clock.pin(1).unwrap().connect(&socket_probe.pin(1).unwrap());
+        socket_clock.pin(1).unwrap().name("clk");
+        let mut board = board.wire();
+        board.plug(Clock::new(1_000_000)).into(socket_clock).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_probe).unwrap();
+        let path = std::env::temp_dir().join(format!("caemu-trace-{}", std::process::id()));
+        let mut board = board.complete_with_log(Log::Trace(path.clone())).unwrap();
+        board.run_until(Delay::from_micros(10)).unwrap();
+        board.close_log().unwrap();
+
+        let mut reader = TraceReader::open(&path).unwrap();
+        assert_eq!(reader.names(), &["clk"]);
+        assert_eq!(reader.value("clk", Delay::from_nanos(4_250)), Ok(Signal::ONE));
+        assert_eq!(reader.value("clk", Delay::from_nanos(4_750)), Ok(Signal::ZERO));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
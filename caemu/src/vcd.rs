@@ -0,0 +1,534 @@
+use crate::bus::Signal;
+use crate::delay::Delay;
+use crate::error::{Error, Result};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Value changes of the variables of a VCD file, by full name such as `caemu.U1.a`
+pub struct Waveform {
+    names: BTreeMap<String, usize>,
+    changes: Vec<Vec<(Delay, Vec<Signal>)>>,
+    widths: Vec<usize>
+}
+
+pub(crate) fn parse_error(line: usize, reason: &str) -> Error {
+    Error::Vcd{line, reason: String::from(reason)}
+}
+
+// picoseconds in one unit of a timescale such as "10 ns" or "1ps"
+pub(crate) fn parse_timescale(text: &str, line: usize) -> Result<u64> {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number : u64 = text[..split].parse().ok().filter(|n| *n > 0).ok_or_else(|| parse_error(line, "bad timescale"))?;
+    let unit = match text[split..].trim() {
+        "s" => 1_000_000_000_000,
+        "ms" => 1_000_000_000,
+        "us" => 1_000_000,
+        "ns" => 1_000,
+        "ps" => 1,
+        _ => return Err(parse_error(line, "timescale below 1 ps or unknown unit"))
+    };
+    number.checked_mul(unit).ok_or_else(|| parse_error(line, "bad timescale"))
+}
+
+/// Bits of a vector change, widened with zeros unless the leftmost bit is x or z
+fn widen(bits: &str, width: usize) -> Vec<Signal> {
+    let mut values : Vec<Signal> = bits.chars().map(Signal::from_char).collect();
+    if values.len() < width {
+        let fill = match values.first() {
+            Some(Signal::UNKNOWN) => Signal::UNKNOWN,
+            Some(Signal::HIGH) => Signal::HIGH,
+            _ => Signal::ZERO
+        };
+        values.splice(0..0, std::iter::repeat_n(fill, width - values.len()));
+    }
+    values.split_off(values.len() - width.min(values.len()))
+}
+
+/// Tokens of a VCD stream with their line, read a line at a time
+struct Tokens<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+    pending: std::vec::IntoIter<String>,
+    error: Option<io::Error>
+}
+
+impl <R: BufRead> Iterator for Tokens<R> {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<(usize, String)> {
+        loop {
+            if let Some(token) = self.pending.next() {
+                return Some((self.line, token));
+            }
+            match self.lines.next()? {
+                Ok(content) => {
+                    self.line += 1;
+                    self.pending = content.split_whitespace().map(String::from).collect::<Vec<_>>().into_iter();
+                },
+                Err(error) => {
+                    self.error = Some(error);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl <R: BufRead> Tokens<R> {
+    /// The tokens up to the next $end
+    fn until_end(&mut self, line: usize) -> Result<Vec<String>> {
+        let mut section = Vec::new();
+        for (_, token) in self.by_ref() {
+            if token == "$end" {
+                return Ok(section);
+            }
+            section.push(token);
+        }
+        Err(self.end(parse_error(line, "missing $end")))
+    }
+
+    /// Error for a stream ending too soon, the I/O error that ended it if any
+    fn end(&mut self, error: Error) -> Error {
+        self.error.take().map(Error::from).unwrap_or(error)
+    }
+}
+
+impl Waveform {
+    pub fn parse(text: &str) -> Result<Self> {
+        Waveform::from_reader(text.as_bytes())
+    }
+
+    /// Reads a VCD stream a line at a time
+    pub fn from_reader<R: BufRead>(input: R) -> Result<Self> {
+        let mut tokens = Tokens{lines: input.lines(), line: 0, pending: Vec::new().into_iter(), error: None};
+        let mut waveform = Waveform{names: BTreeMap::new(), changes: Vec::new(), widths: Vec::new()};
+        let mut ids : HashMap<String, usize> = HashMap::new();
+        let mut scopes : Vec<String> = Vec::new();
+        let mut scale = 1;
+        let mut time = Delay::no_delay();
+
+        while let Some((line, token)) = tokens.next() {
+            match token.as_str() {
+                "$timescale" => scale = parse_timescale(&tokens.until_end(line)?.concat(), line)?,
+                "$scope" => {
+                    let section = tokens.until_end(line)?;
+                    scopes.push(section.get(1).cloned().ok_or_else(|| parse_error(line, "scope without a name"))?);
+                },
+                "$upscope" => {
+                    tokens.until_end(line)?;
+                    scopes.pop();
+                },
+                "$var" => {
+                    let section = tokens.until_end(line)?;
+                    if section.len() < 4 {
+                        return Err(parse_error(line, "incomplete $var"));
+                    }
+                    let width : usize = section[1].parse().map_err(|_| parse_error(line, "bad width"))?;
+                    let name = scopes.iter().chain(std::iter::once(&section[3])).cloned().collect::<Vec<_>>().join(".");
+                    // variables sharing an identifier share their changes
+                    let index = *ids.entry(section[2].clone()).or_insert_with(|| {
+                        waveform.changes.push(Vec::new());
+                        waveform.widths.push(width);
+                        waveform.changes.len() - 1
+                    });
+                    waveform.names.insert(name, index);
+                },
+                // value changes come in these as well
+                "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => (),
+                _ if token.starts_with('$') => {
+                    tokens.until_end(line)?;
+                },
+                _ if token.starts_with('#') => {
+                    let value : u64 = token[1..].parse().map_err(|_| parse_error(line, "bad time"))?;
+                    time = value.checked_mul(scale).map(Delay::from_picos).ok_or_else(|| parse_error(line, "time too large"))?;
+                },
+                _ => {
+                    let (bits, id) = match token.chars().next() {
+                        Some('b') | Some('B') | Some('r') | Some('R') | Some('s') | Some('S') => {
+                            let (_, id) = match tokens.next() {
+                                Some(next) => next,
+                                None => return Err(tokens.end(parse_error(line, "value without identifier")))
+                            };
+                            (&token[1..], id)
+                        },
+                        _ => {
+                            let split = token.char_indices().nth(1).map_or(token.len(), |(i, _)| i);
+                            (&token[..split], String::from(&token[split..]))
+                        }
+                    };
+                    let index = *ids.get(&id).ok_or_else(|| parse_error(line, "unknown identifier"))?;
+                    // real and string values don't exist in caemu, they are read as unknown
+                    let value = match token.chars().next() {
+                        Some('r') | Some('R') | Some('s') | Some('S') => vec![Signal::UNKNOWN; waveform.widths[index]],
+                        _ => widen(bits, waveform.widths[index])
+                    };
+                    waveform.changes[index].push((time, value));
+                }
+            }
+        }
+        match tokens.error {
+            Some(error) => Err(Error::from(error)),
+            None => Ok(waveform)
+        }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref()).map_err(|e| Error::io(path.as_ref(), e))?;
+        Waveform::from_reader(BufReader::new(file))
+    }
+
+    /// Waveform of single nets, with their changes in time order
+    pub(crate) fn from_nets(names: &[String], changes: Vec<Vec<(Delay, Vec<Signal>)>>) -> Self {
+        Waveform {
+            names: names.iter().enumerate().map(|(i, name)| (name.clone(), i)).collect(),
+            widths: vec![1; changes.len()],
+            changes
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(|name| name.as_str())
+    }
+
+    /// Value of a variable at a time, once the changes at that time are applied
+    pub fn value(&self, name: &str, time: Delay) -> Option<Vec<Signal>> {
+        let index = *self.names.get(name)?;
+        let changes = &self.changes[index];
+        let before = changes.partition_point(|(t, _)| *t <= time);
+        Some(match before {
+            0 => vec![Signal::UNKNOWN; self.widths[index]],
+            n => changes[n - 1].1.clone()
+        })
+    }
+
+    /// Changes of a variable to a new value, in time order
+    pub fn changes(&self, name: &str) -> Option<Vec<(Delay, Vec<Signal>)>> {
+        let index = *self.names.get(name)?;
+        Some(self.transitions(index).into_iter().map(|(time, value)| (time, value.to_vec())).collect())
+    }
+
+    /// Changes of a variable, without the ones to the value it already has
+    fn transitions(&self, index: usize) -> Vec<(Delay, &[Signal])> {
+        let mut transitions : Vec<(Delay, &[Signal])> = Vec::new();
+        let unknown = vec![Signal::UNKNOWN; self.widths[index]];
+        for (time, value) in self.changes[index].iter() {
+            // the last change at a given time is the one that stays
+            if transitions.last().map(|t| t.0) == Some(*time) {
+                transitions.pop();
+            }
+            if transitions.last().map_or(&unknown[..], |t| t.1) != &value[..] {
+                transitions.push((*time, value));
+            }
+        }
+        transitions
+    }
+}
+
+fn format_value(value: Option<Vec<Signal>>) -> String {
+    match value {
+        Some(value) => value.iter().map(|s| s.to_char()).collect(),
+        None => String::from("nothing")
+    }
+}
+
+/// First difference found between two waveforms
+#[derive(Debug, PartialEq, Clone)]
+pub struct Divergence {
+    pub name: String,
+    pub time: Delay,
+    /// Values from the most significant bit, as in VCD
+    pub expected: String,
+    pub actual: String
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} diverges at {} ps: expected {}, got {}", self.name, self.time.picoseconds, self.expected, self.actual)
+    }
+}
+
+/// Compares a waveform with a known good one, variable by variable, give or take
+/// the tolerance
+pub struct Compare {
+    tolerance: Delay,
+    ignored: Vec<String>
+}
+
+impl Default for Compare {
+    fn default() -> Self {
+        Compare::new()
+    }
+}
+
+impl Compare {
+    pub fn new() -> Self {
+        Compare{tolerance: Delay::no_delay(), ignored: Vec::new()}
+    }
+
+    /// Largest time difference between matching changes
+    pub fn tolerance(mut self, tolerance: Delay) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Leaves out a variable, or a whole scope with all the variables under it
+    pub fn ignore(mut self, name: &str) -> Self {
+        self.ignored.push(String::from(name));
+        self
+    }
+
+    fn ignored(&self, name: &str) -> bool {
+        self.ignored.iter().any(|ignored| name == ignored
+            || (name.starts_with(ignored.as_str()) && name[ignored.len()..].starts_with('.')))
+    }
+
+    /// The earliest divergence, None when the waveforms match
+    pub fn first_divergence(&self, expected: &Waveform, actual: &Waveform) -> Option<Divergence> {
+        let mut first : Option<Divergence> = None;
+        for (name, index) in expected.names.iter().filter(|(name, _)| !self.ignored(name)) {
+            let time = match actual.names.get(name) {
+                None => Some(Delay::no_delay()),
+                Some(other) => self.diverges(expected.transitions(*index), actual.transitions(*other))
+            };
+            if let Some(time) = time.filter(|time| first.as_ref().map(|d| *time < d.time).unwrap_or(true)) {
+                first = Some(Divergence{name: name.clone(), time,
+                    expected: format_value(expected.value(name, time)),
+                    actual: format_value(actual.value(name, time))});
+            }
+        }
+        first
+    }
+
+    fn diverges(&self, expected: Vec<(Delay, &[Signal])>, actual: Vec<(Delay, &[Signal])>) -> Option<Delay> {
+        for i in 0..expected.len().max(actual.len()) {
+            match (expected.get(i), actual.get(i)) {
+                (Some((te, ve)), Some((ta, va))) => {
+                    let apart = te.picoseconds.abs_diff(ta.picoseconds);
+                    if ve != va || apart > self.tolerance.picoseconds {
+                        return Some((*te).min(*ta));
+                    }
+                },
+                (Some((time, _)), None) | (None, Some((time, _))) => return Some(*time),
+                (None, None) => ()
+            }
+        }
+        None
+    }
+
+    /// Panics with the first divergence, to be used from tests
+    pub fn assert_matches(&self, expected: &Waveform, actual: &Waveform) {
+        if let Some(divergence) = self.first_divergence(expected, actual) {
+            panic!("{}", divergence);
+        }
+    }
+}
+
+/// Log kept in memory, to get the waveform of a board from a test with
+/// `Log::Writer(Box::new(recording.clone()))`
+#[derive(Clone, Default)]
+pub struct Recording {
+    log: Rc<RefCell<Recorded>>
+}
+
+#[derive(Default)]
+struct Recorded {
+    header: Vec<u8>,
+    body: bool,
+    partial: Vec<u8>,
+    window: Option<u64>,
+    /// Last value of each identifier before the window, the time it changed forgotten
+    before: BTreeMap<String, String>,
+    lines: VecDeque<(u64, Vec<u8>)>
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Recording::default()
+    }
+
+    /// Only keeps the changes of the last `window` of time, and the values from before
+    pub fn set_window(&self, window: Delay) {
+        self.log.borrow_mut().window = Some(window.picoseconds);
+    }
+
+    /// What was written and kept
+    pub fn bytes(&self) -> Vec<u8> {
+        let log = self.log.borrow();
+        let mut bytes = log.header.clone();
+        if !log.before.is_empty() {
+            let values : Vec<&str> = log.before.values().map(|value| value.as_str()).collect();
+            bytes.extend_from_slice(format!("$dumpvars {} $end\n", values.join(" ")).as_bytes());
+        }
+        for (_, line) in log.lines.iter() {
+            bytes.extend_from_slice(line);
+        }
+        bytes.extend_from_slice(&log.partial);
+        bytes
+    }
+
+    pub fn waveform(&self) -> Result<Waveform> {
+        Waveform::from_reader(&self.bytes()[..])
+    }
+}
+
+impl Recorded {
+    fn push(&mut self, line: Vec<u8>) {
+        if !self.body {
+            self.body = line.windows(15).any(|w| w == b"$enddefinitions");
+            self.header.extend_from_slice(&line);
+            return;
+        }
+        // lines without a time, such as after a rewind, go with the previous one
+        let time = line.strip_prefix(b"#")
+            .and_then(|rest| std::str::from_utf8(rest).ok())
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|time| time.parse().ok())
+            .unwrap_or_else(|| self.lines.back().map_or(0, |(time, _)| *time));
+        self.lines.push_back((time, line));
+        if let Some(window) = self.window {
+            let start = time.saturating_sub(window);
+            while self.lines.front().filter(|(time, _)| *time <= start).is_some() {
+                let (_, line) = self.lines.pop_front().unwrap();
+                fold(&mut self.before, &String::from_utf8_lossy(&line));
+            }
+        }
+    }
+}
+
+/// Keeps the value each identifier gets on a line of changes
+fn fold(values: &mut BTreeMap<String, String>, line: &str) {
+    if line.starts_with("$comment") {
+        return;
+    }
+    let mut tokens = line.split_whitespace().filter(|token| !token.starts_with('$') && !token.starts_with('#'));
+    while let Some(token) = tokens.next() {
+        match token.chars().next() {
+            Some('b') | Some('B') | Some('r') | Some('R') | Some('s') | Some('S') => {
+                if let Some(id) = tokens.next() {
+                    values.insert(String::from(id), format!("{} {}", token, id));
+                }
+            },
+            _ => {
+                let split = token.char_indices().nth(1).map_or(token.len(), |(i, _)| i);
+                values.insert(String::from(&token[split..]), String::from(token));
+            }
+        }
+    }
+}
+
+impl Write for Recording {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = self.log.borrow_mut();
+        log.partial.extend_from_slice(buf);
+        while let Some(end) = log.partial.iter().position(|b| *b == b'\n') {
+            let line : Vec<u8> = log.partial.drain(..=end).collect();
+            log.push(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, CompleteBoard};
+    use crate::bus::Signal;
+    use crate::clock::Clock;
+    use crate::delay::Delay;
+    use crate::error::Error;
+    use crate::logger::Log;
+    use crate::tester::ProbeOutput;
+    use crate::vcd::{Compare, Divergence, Recording, Waveform};
+
+    #[test]
+    fn parse_test() {
+        let text = "$date today $end\n$timescale 10 ns $end\n\
+            $scope module top $end\n$var wire 1 ! clk $end\n\
+            $scope module U1 $end\n$var wire 4 \" d [3:0] $end\n$var wire 1 ! c $end\n$upscope $end\n\
+            $upscope $end\n$enddefinitions $end\n\
+            $dumpvars\n0!\nbx \"\n$end\n#2\n1! b101 \"\n#5 bz1 \"\n";
+        let waveform = Waveform::parse(text).unwrap();
+        assert_eq!(waveform.names().collect::<Vec<_>>(), vec!["top.U1.c", "top.U1.d", "top.clk"]);
+        use Signal::*;
+        assert_eq!(waveform.value("top.U1.d", Delay::from_nanos(10)), Some(vec![UNKNOWN; 4]));
+        assert_eq!(waveform.value("top.U1.d", Delay::from_nanos(20)), Some(vec![ZERO, ONE, ZERO, ONE]));
+        assert_eq!(waveform.value("top.U1.d", Delay::from_nanos(50)), Some(vec![HIGH, HIGH, HIGH, ONE]));
+        assert_eq!(waveform.value("top.U1.c", Delay::from_nanos(25)), Some(vec![ONE]));
+        assert_eq!(waveform.value("top.nothing", Delay::no_delay()), None);
+        assert_eq!(Waveform::parse("#1\n1?\n").err(), Some(Error::Vcd{line: 2, reason: String::from("unknown identifier")}));
+        assert_eq!(Waveform::parse("$timescale 0 ns $end\n").err(), Some(Error::Vcd{line: 1, reason: String::from("bad timescale")}));
+        assert_eq!(Waveform::parse("#1\né!\n").err(), Some(Error::Vcd{line: 2, reason: String::from("unknown identifier")}));
+        assert_eq!(Waveform::parse("$timescale 1 ms $end\n#18446744073709551\n").err(),
+            Some(Error::Vcd{line: 2, reason: String::from("time too large")}));
+        assert!(Waveform::from_reader(&b"$var wire 1 ! a $end\n#1 1!\n\xff\n"[..]).is_err());
+    }
+
+    fn clock_waveform(phase: Delay) -> Waveform {
+        let mut board = Board::new();
+        let socket_clock = board.socket(1);
+        let socket_probe = board.socket(1);
+        socket_clock.pin(1).unwrap().connect(&socket_probe.pin(1).unwrap());
+        socket_clock.pin(1).unwrap().name("clk");
+        socket_probe.name("probe");
+        let mut board = board.wire();
+        let clock = Clock::new(1_000_000);
+        clock.borrow_mut().set_phase(phase);
+        board.plug(clock).into(socket_clock).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_probe).unwrap();
+        let recording = Recording::new();
+        let mut board : CompleteBoard = board.complete_with_log(Log::Writer(Box::new(recording.clone()))).unwrap();
+        board.run_until(Delay::from_micros(5)).unwrap();
+        recording.waveform().unwrap()
+    }
+
+    #[test]
+    fn compare_test() {
+        let golden = clock_waveform(Delay::from_nanos(100));
+        Compare::new().assert_matches(&golden, &clock_waveform(Delay::from_nanos(100)));
+
+        // every edge 5 ns late
+        let late = clock_waveform(Delay::from_nanos(105));
+        assert_eq!(Compare::new().first_divergence(&golden, &late), Some(Divergence{
+            name: String::from("caemu.U1.clk"),
+            time: Delay::from_nanos(100),
+            expected: String::from("1"),
+            actual: String::from("0")
+        }));
+        Compare::new().tolerance(Delay::from_nanos(5)).assert_matches(&golden, &late);
+        Compare::new().ignore("caemu").assert_matches(&golden, &late);
+    }
+
+    #[test]
+    fn window_test() {
+        let golden = clock_waveform(Delay::from_nanos(100));
+        let mut board = Board::new();
+        let socket_clock = board.socket(1);
+        let socket_probe = board.socket(1);
+        socket_clock.pin(1).unwrap().connect(&socket_probe.pin(1).unwrap());
+        let mut board = board.wire();
+        let clock = Clock::new(1_000_000);
+        clock.borrow_mut().set_phase(Delay::from_nanos(100));
+        board.plug(clock).into(socket_clock).unwrap();
+        board.plug(ProbeOutput::new()).into(socket_probe).unwrap();
+        let recording = Recording::new();
+        recording.set_window(Delay::from_micros(1));
+        let mut board : CompleteBoard = board.complete_with_log(Log::Writer(Box::new(recording.clone()))).unwrap();
+        board.run_until(Delay::from_micros(5)).unwrap();
+
+        // two edges a microsecond
+        assert!(recording.log.borrow().lines.len() <= 3);
+        let waveform = recording.waveform().unwrap();
+        for time in (4_000..=5_000).step_by(50).map(Delay::from_nanos) {
+            assert_eq!(waveform.value("caemu.U1.clk", time), golden.value("caemu.U1.clk", time), "at {:?}", time);
+        }
+    }
+}